@@ -0,0 +1,472 @@
+use poise::serenity_prelude::{
+    ActionRowComponent, ButtonStyle, ChannelId, ComponentInteraction, Context, CreateActionRow,
+    CreateButton, CreateInputText, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateModal, GuildId, Http, InputTextStyle, Interaction, ModalInteraction, Permissions,
+    Timestamp, UserId,
+};
+
+use crate::{
+    structs::{ActionTypes, Appeal, AppealStatus, Case, CommandCategory},
+    utils::{granted_access, Valeriyya},
+    Data,
+};
+
+const APPEAL_OPEN: &str = "appeal-open";
+const APPEAL_SUBMIT: &str = "appeal-submit";
+const APPEAL_ACCEPT: &str = "appeal-accept";
+const APPEAL_DENY: &str = "appeal-deny";
+const APPEAL_REASON: &str = "appeal-reason";
+
+/// Parses a custom id in the `<kind>:<guild id>:<id>` format used by the appeal components.
+fn parse_custom_id(custom_id: &str) -> Option<(&str, u64, u32)> {
+    let mut parts = custom_id.splitn(3, ':');
+    let kind = parts.next()?;
+    let guild_id = parts.next()?.parse::<u64>().ok()?;
+    let id = parts.next()?.parse::<u32>().ok()?;
+    Some((kind, guild_id, id))
+}
+
+/// Sends the punished user a DM about the case, with an appeal button if the guild has an appeals channel.
+pub async fn send_appeal_dm(
+    http: &Http,
+    user_id: UserId,
+    guild_name: &str,
+    case: &Case,
+    appealable: bool,
+) {
+    let action = match case.action {
+        ActionTypes::Ban => "banned from",
+        ActionTypes::Mute => "muted in",
+        _ => return,
+    };
+
+    let mut message = Valeriyya::msg_reply().add_embed(
+        Valeriyya::embed()
+            .title("Moderation notice")
+            .description(format!(
+                "You have been {} **{}**.\nReason: `{}`",
+                action, guild_name, case.reason
+            ))
+            .footer(Valeriyya::reply_footer(format!("Case {}", case.id))),
+    );
+
+    if appealable {
+        message = message.components(vec![CreateActionRow::Buttons(
+            vec![
+                CreateButton::new(format!("{}:{}:{}", APPEAL_OPEN, case.guild_id, case.id))
+                    .style(ButtonStyle::Primary)
+                    .label("Appeal"),
+            ]
+            .into(),
+        )]);
+    }
+
+    if let Err(err) = user_id.direct_message(http, message).await {
+        tracing::warn!("Failed to send the appeal DM to {}: {}", user_id, err);
+    }
+}
+
+/// Tells the user the action from `send_appeal_dm` didn't go through, so the case and its
+/// appeal button don't exist.
+pub async fn send_failed_action_dm(http: &Http, user_id: UserId, guild_name: &str, case: &Case) {
+    let action = match case.action {
+        ActionTypes::Ban => "ban from",
+        ActionTypes::Mute => "mute in",
+        _ => return,
+    };
+
+    let message = Valeriyya::msg_reply().add_embed(
+        Valeriyya::embed()
+            .title("Moderation notice")
+            .description(format!(
+                "The {} **{}** didn't go through, you can ignore the notice above.",
+                action, guild_name
+            )),
+    );
+
+    if let Err(err) = user_id.direct_message(http, message).await {
+        tracing::warn!(
+            "Failed to send the failed action DM to {}: {}",
+            user_id,
+            err
+        );
+    }
+}
+
+pub async fn handle_appeal_interaction(ctx: &Context, data: &Data, interaction: &Interaction) {
+    let result = match interaction {
+        Interaction::Component(component) => match parse_custom_id(&component.data.custom_id) {
+            Some((APPEAL_OPEN, guild_id, case_id)) => {
+                open_appeal(ctx, data, component, guild_id, case_id).await
+            }
+            Some((APPEAL_ACCEPT, guild_id, appeal_id)) => {
+                review_appeal(ctx, data, component, guild_id, appeal_id, true).await
+            }
+            Some((APPEAL_DENY, guild_id, appeal_id)) => {
+                review_appeal(ctx, data, component, guild_id, appeal_id, false).await
+            }
+            _ => Ok(()),
+        },
+        Interaction::Modal(modal) => match parse_custom_id(&modal.data.custom_id) {
+            Some((APPEAL_SUBMIT, guild_id, case_id)) => {
+                submit_appeal(ctx, data, modal, guild_id, case_id).await
+            }
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    };
+
+    if let Err(err) = result {
+        tracing::error!("Error while handling an appeal interaction: {:?}", err);
+    }
+}
+
+async fn open_appeal(
+    ctx: &Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    guild_id: u64,
+    case_id: u32,
+) -> Result<(), crate::Error> {
+    let db = Valeriyya::get_database(data, guild_id).await?;
+    let user_id = component.user.id.to_string();
+
+    let response = match db.cases.iter().find(|c| c.id == case_id) {
+        Some(case) if case.target_id != user_id => Some("This case doesn't belong to you."),
+        Some(_) if db.channels.appeals.is_none() => {
+            Some("This guild is no longer accepting appeals.")
+        }
+        Some(_) if db.appeals.iter().any(|a| a.case_id == case_id) => {
+            Some("You have already appealed this case.")
+        }
+        Some(_) => None,
+        None => Some("This case no longer exists."),
+    };
+
+    if let Some(content) = response {
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Modal(
+                CreateModal::new(
+                    format!("{}:{}:{}", APPEAL_SUBMIT, guild_id, case_id),
+                    format!("Appeal for case {}", case_id),
+                )
+                .components(vec![CreateActionRow::InputText(
+                    CreateInputText::new(
+                        InputTextStyle::Paragraph,
+                        "Why should this be lifted?",
+                        APPEAL_REASON,
+                    )
+                    .max_length(1000),
+                )]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn submit_appeal(
+    ctx: &Context,
+    data: &Data,
+    modal: &ModalInteraction,
+    guild_id: u64,
+    case_id: u32,
+) -> Result<(), crate::Error> {
+    let reason = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(text) if &*text.custom_id == APPEAL_REASON => {
+                text.value.as_deref().map(str::to_string)
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
+
+    let (case, appeals_channel) = match (
+        db.cases.iter().find(|c| c.id == case_id).cloned(),
+        db.channels.appeals.clone(),
+    ) {
+        (Some(case), Some(channel)) if !db.appeals.iter().any(|a| a.case_id == case_id) => {
+            (case, channel)
+        }
+        _ => {
+            modal
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("This appeal can't be submitted."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let appeal_id = db.appeals_number + 1;
+    db = db.add_appeal(Appeal {
+        id: appeal_id,
+        case_id,
+        user_id: modal.user.id.to_string(),
+        reason: reason.clone(),
+        status: AppealStatus::Pending,
+        date: Timestamp::unix_timestamp(&Timestamp::now()),
+        message: None,
+        reviewer_id: None,
+    });
+
+    let sent_msg = ChannelId::new(appeals_channel.parse::<u64>()?)
+        .widen()
+        .send_message(
+            &ctx.http,
+            Valeriyya::msg_reply()
+                .add_embed(
+                    Valeriyya::embed()
+                        .author(
+                            Valeriyya::reply_author(format!(
+                                "{} ({})",
+                                modal.user.tag(),
+                                modal.user.id
+                            ))
+                            .icon_url(modal.user.face()),
+                        )
+                        .title(format!("Appeal {}", appeal_id))
+                        .description(format!(
+                            "Case: `{}`\nAction: `{:?}`\nCase reason: `{}`\n\n{}",
+                            case.id, case.action, case.reason, reason
+                        ))
+                        .footer(Valeriyya::reply_footer("Pending")),
+                )
+                .components(vec![CreateActionRow::Buttons(
+                    vec![
+                        CreateButton::new(format!("{}:{}:{}", APPEAL_ACCEPT, guild_id, appeal_id))
+                            .style(ButtonStyle::Success)
+                            .label("Accept"),
+                        CreateButton::new(format!("{}:{}:{}", APPEAL_DENY, guild_id, appeal_id))
+                            .style(ButtonStyle::Danger)
+                            .label("Deny"),
+                    ]
+                    .into(),
+                )]),
+        )
+        .await?;
+
+    db = db.set_appeal_message(appeal_id, Some(sent_msg.id.to_string()));
+    db.execute(data).await;
+
+    modal
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Your appeal has been submitted and will be reviewed by the staff."),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn review_appeal(
+    ctx: &Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    guild_id: u64,
+    appeal_id: u32,
+    accepted: bool,
+) -> Result<(), crate::Error> {
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
+
+    let appeal = match db.appeals.iter().find(|a| a.id == appeal_id) {
+        Some(appeal) if appeal.status == AppealStatus::Pending => appeal.clone(),
+        _ => {
+            component
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("This appeal has already been reviewed.")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let case = match db.cases.iter().find(|c| c.id == appeal.case_id) {
+        Some(case) => case.clone(),
+        None => {
+            component
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!(
+                                "Case with the id: {} doesn't exist",
+                                appeal.case_id
+                            ))
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let (required_permission, lifted_action) = match case.action {
+        ActionTypes::Mute => (Permissions::MODERATE_MEMBERS, ActionTypes::Unmute),
+        _ => (Permissions::BAN_MEMBERS, ActionTypes::Unban),
+    };
+
+    // Staff roles and roles granted the moderation category can review appeals too.
+    let permitted = match component.member.as_ref() {
+        Some(member) => {
+            granted_access(
+                data,
+                GuildId::new(guild_id),
+                member,
+                member.permissions.unwrap_or_else(Permissions::empty),
+                CommandCategory::Moderation,
+                required_permission,
+            )
+            .await?
+        }
+        None => false,
+    };
+
+    if !permitted {
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You don't have the permissions to review this appeal.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let guild = GuildId::new(guild_id);
+    let target_id = UserId::new(appeal.user_id.parse::<u64>()?);
+    let staff = &component.user;
+
+    if accepted {
+        let reason = format!("Appeal {} accepted", appeal.id);
+
+        match lifted_action {
+            ActionTypes::Unmute => {
+                let mut member = guild.member(&ctx.http, target_id).await?;
+                member.enable_communication(&ctx.http).await?;
+            }
+            _ => {
+                guild.unban(&ctx.http, target_id, Some(&reason)).await?;
+            }
+        }
+
+        let case_number = db.cases_number + 1;
+        let message = if let Some(logs) = &db.channels.logs {
+            let sent_msg = ChannelId::new(logs.parse::<u64>()?)
+                .widen()
+                .send_message(
+                    &ctx.http,
+                    Valeriyya::msg_reply().add_embed(
+                        Valeriyya::embed()
+                            .author(
+                                Valeriyya::reply_author(format!("{} ({})", staff.tag(), staff.id))
+                                    .icon_url(staff.face()),
+                            )
+                            .description(format!(
+                                "Member: `{}`\nAction: `{:?}`\nReason: `{}`\nReference: `{}`",
+                                target_id, lifted_action, reason, case.id
+                            ))
+                            .footer(Valeriyya::reply_footer(format!("Case {}", case_number))),
+                    ),
+                )
+                .await?;
+            Some(sent_msg.id.to_string())
+        } else {
+            None
+        };
+
+        db = db.add_cases(Case {
+            id: case_number,
+            action: lifted_action,
+            guild_id: guild_id.to_string(),
+            staff_id: staff.id.to_string(),
+            target_id: target_id.to_string(),
+            date: Timestamp::unix_timestamp(&Timestamp::now()),
+            reason,
+            reference: Some(case.id),
+            expiration: None,
+            message,
+        });
+    }
+
+    let status = if accepted {
+        AppealStatus::Accepted
+    } else {
+        AppealStatus::Denied
+    };
+
+    db = db.update_appeal(appeal.id, status.clone(), Some(staff.id.to_string()));
+    db.execute(data).await;
+
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(
+                        Valeriyya::embed()
+                            .author(
+                                Valeriyya::reply_author(format!("{} ({})", staff.tag(), staff.id))
+                                    .icon_url(staff.face()),
+                            )
+                            .title(format!("Appeal {}", appeal.id))
+                            .description(format!(
+                                "Member: <@{}>\nCase: `{}`\nAction: `{:?}`\n\n{}",
+                                appeal.user_id, case.id, case.action, appeal.reason
+                            ))
+                            .footer(Valeriyya::reply_footer(format!("{:?}", status))),
+                    )
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    let _ = target_id
+        .direct_message(
+            &ctx.http,
+            Valeriyya::msg_reply().content(format!(
+                "Your appeal for case {} has been {}.",
+                case.id,
+                if accepted { "accepted" } else { "denied" }
+            )),
+        )
+        .await;
+
+    Ok(())
+}
@@ -8,9 +8,9 @@ pub async fn star(
     ctx: Context<'_>,
     #[description = "Message to star "] msg: serenity::Message,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild_id = ctx.guild_id().unwrap().get();
-    let guild_db = Valeriyya::get_database(database, guild_id).await?;
+    let guild_db = Valeriyya::get_database(data, guild_id).await?;
 
     let starboard_channel = guild_db.channels.starboard;
     if let Some(starboard_channel) = starboard_channel {
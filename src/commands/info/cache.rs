@@ -0,0 +1,21 @@
+use crate::{utils::Valeriyya, Context, Error};
+
+#[doc = "Shows the guild settings cache metrics. (Owner only)"]
+#[poise::command(prefix_command, slash_command, hide_in_help, owners_only)]
+pub async fn cache(ctx: Context<'_>) -> Result<(), Error> {
+    let stats = ctx.data().guild_cache.stats().await;
+
+    ctx.send(
+        Valeriyya::reply_default().embed(
+            Valeriyya::embed()
+                .title("Guild cache")
+                .field("Entries", stats.entries.to_string(), true)
+                .field("Hits", stats.hits.to_string(), true)
+                .field("Misses", stats.misses.to_string(), true)
+                .field("Hit rate", format!("{:.2}%", stats.hit_rate()), true),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -1,3 +1,3 @@
 use crate::import;
 
-import![help, register, reminder];
+import![help, register, reminder, cache];
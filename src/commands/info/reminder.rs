@@ -21,10 +21,10 @@ pub async fn reminder(_ctx: Context<'_>) -> Result<(), Error> {
 
 #[poise::command(slash_command, category = "Info", prefix_command, track_edits)]
 pub async fn create(ctx: Context<'_>, message: String, time: String) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild_id = ctx.guild_id().unwrap().get();
 
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
 
     let human_time = parse_duration(&time).expect("There was an error calculating the human time!");
     let now = Utc::now();
@@ -42,7 +42,7 @@ pub async fn create(ctx: Context<'_>, message: String, time: String) -> Result<(
     db.reminder_count += 1;
     db = db.add_reminder(reminder);
 
-    db.execute(database).await;
+    db.execute(data).await;
 
     ctx.reply("Reminder created!").await?;
     Ok(())
@@ -50,11 +50,11 @@ pub async fn create(ctx: Context<'_>, message: String, time: String) -> Result<(
 
 #[poise::command(slash_command, category = "Info", prefix_command, track_edits)]
 pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild = ctx.guild_id().unwrap();
     let guild_id = guild.get();
 
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
     let user_id = ctx.author().id.get();
 
     let mut reminders: Vec<Reminder> = db.get_reminders_for_user(user_id);
@@ -118,7 +118,7 @@ pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
         }
     }
 
-    db.execute(database).await;
+    db.execute(data).await;
 
     Ok(())
 }
@@ -1,15 +1,32 @@
 use crate::{
+    appeals::{send_appeal_dm, send_failed_action_dm},
     structs::{ActionTypes, Case},
     utils::{member_managable, Valeriyya},
     Context, Error,
 };
-use poise::serenity_prelude::{ChannelId, Member, Timestamp, UserId};
+use poise::serenity_prelude::{ChannelId, Member, Permissions, Timestamp, UserId};
+
+/// The user was told about the ban before it happened, since a DM may not reach them after.
+/// Takes that back when the ban failed, the case was never saved.
+async fn ban_failed(
+    ctx: Context<'_>,
+    user_id: UserId,
+    guild_name: &str,
+    case: &Case,
+    err: poise::serenity_prelude::Error,
+) -> Result<(), Error> {
+    send_failed_action_dm(ctx.http(), user_id, guild_name, case).await;
+    ctx.send(Valeriyya::reply(format!("I couldn't ban <@{}>: {}", user_id, err)).ephemeral(true))
+        .await?;
+    Ok(())
+}
 
 #[doc = "Bans a member from the guild."]
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "BAN_MEMBERS",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::BAN_MEMBERS",
     prefix_command,
     track_edits
 )]
@@ -24,12 +41,13 @@ pub async fn ban(
     #[rest]
     reason: Option<String>,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild = ctx.guild_id().unwrap();
     let guild_id = guild.get();
     let icon_url = ctx.guild().unwrap().icon_url().unwrap_or(String::from(""));
+    let guild_name = ctx.guild().unwrap().name.to_string();
 
-    let mut guild_db = Valeriyya::get_database(database, guild_id).await?;
+    let mut guild_db = Valeriyya::get_database(data, guild_id).await?;
     let case_number = guild_db.cases_number + 1;
     let reason_default = reason.unwrap_or(format!(
         "Use /reason {} <...reason> to set a reason for this case.",
@@ -56,7 +74,30 @@ pub async fn ban(
             )
             .await?;
         }
-        member.ban(ctx.http(), 7, Some(&reason_default)).await?;
+        let mut case = Case {
+            id: case_number,
+            action: ActionTypes::Ban,
+            guild_id: guild_id.to_string(),
+            staff_id: ctx.author().id.to_string(),
+            target_id: member.user.id.to_string(),
+            date: Timestamp::unix_timestamp(&Timestamp::now()),
+            reason: reason_default.to_string(),
+            message: None,
+            expiration: None,
+            reference: None,
+        };
+
+        send_appeal_dm(
+            ctx.http(),
+            member.user.id,
+            &guild_name,
+            &case,
+            guild_db.channels.appeals.is_some(),
+        )
+        .await;
+        if let Err(err) = member.ban(ctx.http(), 7, Some(&reason_default)).await {
+            return ban_failed(ctx, member.user.id, &guild_name, &case, err).await;
+        }
 
         let message = if guild_db.channels.logs.as_ref().is_some() {
             let sent_msg = ChannelId::new(
@@ -99,18 +140,8 @@ pub async fn ban(
             None
         };
 
-        guild_db = guild_db.add_cases(Case {
-            id: case_number,
-            action: ActionTypes::Ban,
-            guild_id: guild_id.to_string(),
-            staff_id: ctx.author().id.to_string(),
-            target_id: member.user.id.to_string(),
-            date: Timestamp::unix_timestamp(&Timestamp::now()),
-            reason: reason_default.to_string(),
-            message,
-            expiration: None,
-            reference: None,
-        });
+        case.message = message;
+        guild_db = guild_db.add_cases(case);
 
         ctx.say(format!(
             "{:?} has been banned by {:?}!",
@@ -131,9 +162,33 @@ pub async fn ban(
             )
             .await?;
         }
-        guild
+        let mut case = Case {
+            id: case_number,
+            action: ActionTypes::Ban,
+            guild_id: guild_id.to_string(),
+            staff_id: ctx.author().id.to_string(),
+            target_id: user_id.to_string(),
+            date: Timestamp::unix_timestamp(&Timestamp::now()),
+            reason: reason_default.to_string(),
+            message: None,
+            expiration: None,
+            reference: None,
+        };
+
+        send_appeal_dm(
+            ctx.http(),
+            user_id,
+            &guild_name,
+            &case,
+            guild_db.channels.appeals.is_some(),
+        )
+        .await;
+        if let Err(err) = guild
             .ban(ctx.http(), user_id, 7, Some(&reason_default))
-            .await?;
+            .await
+        {
+            return ban_failed(ctx, user_id, &guild_name, &case, err).await;
+        }
 
         let message = if guild_db.channels.logs.as_ref().is_some() {
             let sent_msg = ChannelId::new(
@@ -176,18 +231,8 @@ pub async fn ban(
             None
         };
 
-        guild_db = guild_db.add_cases(Case {
-            id: case_number,
-            action: ActionTypes::Ban,
-            guild_id: guild_id.to_string(),
-            staff_id: ctx.author().id.to_string(),
-            target_id: user_id.to_string(),
-            date: Timestamp::unix_timestamp(&Timestamp::now()),
-            reason: reason_default.to_string(),
-            message,
-            expiration: None,
-            reference: None,
-        });
+        case.message = message;
+        guild_db = guild_db.add_cases(case);
 
         ctx.say(format!(
             "Member with the the id: {} has been banned by {:?}!",
@@ -196,6 +241,6 @@ pub async fn ban(
         ))
         .await?;
     }
-    guild_db.execute(database).await;
+    guild_db.execute(data).await;
     Ok(())
 }
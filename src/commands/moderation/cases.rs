@@ -1,4 +1,4 @@
-use poise::serenity_prelude::{CreateEmbed, Member, Timestamp, UserId};
+use poise::serenity_prelude::{CreateEmbed, Member, Permissions, Timestamp, UserId};
 
 use crate::{
     structs::{ActionTypes, Case},
@@ -18,7 +18,8 @@ pub enum OptionChoices {
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "MANAGE_GUILD",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::MANAGE_GUILD",
     prefix_command,
     track_edits
 )]
@@ -27,11 +28,11 @@ pub async fn cases(
     #[description = "What to do with the case."] option: OptionChoices,
     #[description = "The id of the case."] id: u32,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
 
     let guild_id = ctx.guild_id().unwrap().get();
 
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
     let staff = get_guild_member(ctx).await?.unwrap();
 
     if let OptionChoices::Show = option {
@@ -84,7 +85,7 @@ pub async fn cases(
         )
         .await?;
     }
-    db.execute(database).await;
+    db.execute(data).await;
     Ok(())
 }
 
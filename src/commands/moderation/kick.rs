@@ -1,4 +1,4 @@
-use poise::serenity_prelude::{ChannelId, Member, Timestamp};
+use poise::serenity_prelude::{ChannelId, Member, Permissions, Timestamp};
 
 use crate::{
     structs::{ActionTypes, Case},
@@ -10,7 +10,8 @@ use crate::{
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "KICK_MEMBERS",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::KICK_MEMBERS",
     prefix_command,
     track_edits
 )]
@@ -21,11 +22,11 @@ pub async fn kick(
     #[rest]
     reason: Option<String>,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild = ctx.guild_id().unwrap();
     let guild_id = guild.get();
 
-    let mut guild_db = Valeriyya::get_database(database, guild_id).await?;
+    let mut guild_db = Valeriyya::get_database(data, guild_id).await?;
     let case_number = guild_db.cases_number + 1;
     let reason_default = reason.unwrap_or_else(|| {
         format!(
@@ -106,6 +107,6 @@ pub async fn kick(
     ctx.say(format!("{} has been kicked by {}!", member, ctx.author()))
         .await?;
 
-    guild_db.execute(database).await;
+    guild_db.execute(data).await;
     Ok(())
 }
@@ -1,15 +1,17 @@
 use crate::{
+    appeals::send_appeal_dm,
     structs::{ActionTypes, Case},
     utils::{member_managable, Valeriyya},
     Context, Error,
 };
-use poise::serenity_prelude::{ChannelId, Member, Timestamp};
+use poise::serenity_prelude::{ChannelId, Member, Permissions, Timestamp};
 
 #[doc = "Mutes a member for a specified time."]
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "MODERATE_MEMBERS",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::MODERATE_MEMBERS",
     prefix_command,
     track_edits
 )]
@@ -33,11 +35,11 @@ pub async fn mute(
         Timestamp::from_unix_timestamp(Timestamp::unix_timestamp(&Timestamp::now()) + string_time)
             .ok();
 
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild = ctx.guild_id().unwrap();
     let guild_id = guild.get();
 
-    let mut guild_db = Valeriyya::get_database(database, guild_id).await?;
+    let mut guild_db = Valeriyya::get_database(data, guild_id).await?;
     let case_number = guild_db.cases_number + 1;
     let reason_default = reason.unwrap_or_else(|| {
         format!(
@@ -117,7 +119,7 @@ pub async fn mute(
         None
     };
 
-    guild_db = guild_db.add_cases(Case {
+    let case = Case {
         id: case_number,
         action: ActionTypes::Mute,
         guild_id: guild_id.to_string(),
@@ -128,12 +130,24 @@ pub async fn mute(
         expiration: Some(timestamp.unwrap().unix_timestamp()),
         message,
         reference: None,
-    });
+    };
+
+    let guild_name = ctx.guild().unwrap().name.to_string();
+    send_appeal_dm(
+        ctx.http(),
+        member.user.id,
+        &guild_name,
+        &case,
+        guild_db.channels.appeals.is_some(),
+    )
+    .await;
+
+    guild_db = guild_db.add_cases(case);
 
     ctx.say(format!("{} has been muted by {}!", member, ctx.author()))
         .await?;
 
-    guild_db.execute(database).await;
+    guild_db.execute(data).await;
     Ok(())
 }
 
@@ -1,4 +1,4 @@
-use poise::serenity_prelude::{ChannelId, MessageId, Timestamp, UserId};
+use poise::serenity_prelude::{ChannelId, MessageId, Permissions, Timestamp, UserId};
 
 use crate::{
     structs::{ActionTypes, CaseUpdateAction, CaseUpdateValue},
@@ -10,7 +10,8 @@ use crate::{
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "MANAGE_GUILD",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::MANAGE_GUILD",
     prefix_command,
     track_edits
 )]
@@ -21,10 +22,10 @@ pub async fn reason(
     #[rest]
     reason: String,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild = ctx.guild_id().unwrap();
     let guild_id = guild.get();
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
 
     let case_find = match db.cases.iter().find(|c| c.id == case) {
         Some(c) => c.clone(),
@@ -110,6 +111,6 @@ pub async fn reason(
                 .await?;
         };
     }
-    db.execute(database).await;
+    db.execute(data).await;
     Ok(())
 }
@@ -1,4 +1,4 @@
-use poise::serenity_prelude::{ChannelId, MessageId, Timestamp, UserId};
+use poise::serenity_prelude::{ChannelId, MessageId, Permissions, Timestamp, UserId};
 
 use crate::{
     structs::{ActionTypes, CaseUpdateAction, CaseUpdateValue},
@@ -10,7 +10,8 @@ use crate::{
 #[poise::command(
     slash_command,
     category = "Moderation",
-    default_member_permissions = "MANAGE_GUILD",
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::MANAGE_GUILD",
     prefix_command,
     track_edits
 )]
@@ -19,9 +20,9 @@ pub async fn reference(
     #[description = "The case to assign a reference."] case: u32,
     #[description = "The reference case"] reference: u32,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild_id = ctx.guild_id().unwrap().get();
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
 
     let db_cases = db.cases.clone();
 
@@ -136,6 +137,6 @@ pub async fn reference(
 
     ctx.send(Valeriyya::reply(format!("Updated case with the id: {case}")).ephemeral(true))
         .await?;
-    db.execute(database).await;
+    db.execute(data).await;
     Ok(())
 }
@@ -0,0 +1,40 @@
+use crate::{
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Removes every upcoming song from the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let removed = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().modify_queue(|queue| {
+            let upcoming = queue.len().min(1);
+            queue.drain(upcoming..).collect::<Vec<_>>()
+        }),
+        None => Vec::new(),
+    };
+
+    ctx.data()
+        .music_sessions
+        .update(guild_id.get(), |session| session.clears += 1)
+        .await;
+    for queued in &removed {
+        let _ = queued.stop();
+    }
+
+    ctx.send(Valeriyya::reply(format!(
+        "Removed {} songs from the queue.",
+        removed.len()
+    )))
+    .await?;
+
+    Ok(())
+}
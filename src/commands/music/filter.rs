@@ -0,0 +1,85 @@
+use crate::{
+    filters::{AudioFilter, FilterPreset, MAX_FACTOR, MIN_FACTOR},
+    structs::{QueueContext, TrackMetadata},
+    utils::{dj_check, restart_queue, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Applies an audio filter to the music in this guild."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn filter(
+    ctx: Context<'_>,
+    #[description = "The filter preset, off removes every filter."] preset: Option<FilterPreset>,
+    #[description = "The speed between 0.5 and 2."]
+    #[min = 0.5]
+    #[max = 2.0]
+    speed: Option<f64>,
+    #[description = "The pitch between 0.5 and 2."]
+    #[min = 0.5]
+    #[max = 2.0]
+    pitch: Option<f64>,
+) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    let session = data.music_sessions.get(guild_id.get()).await;
+
+    if preset.is_none() && speed.is_none() && pitch.is_none() {
+        ctx.send(Valeriyya::reply(format!(
+            "The current filter is: {}",
+            session.filter
+        )))
+        .await?;
+        return Ok(());
+    }
+
+    if [speed, pitch]
+        .into_iter()
+        .flatten()
+        .any(|factor| !(MIN_FACTOR..=MAX_FACTOR).contains(&factor))
+    {
+        ctx.send(
+            Valeriyya::reply("The speed and pitch have to be between 0.5 and 2.").ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut filter = preset.map_or(session.filter, AudioFilter::from_preset);
+    if let Some(speed) = speed {
+        filter = filter.set_speed(speed);
+    }
+    if let Some(pitch) = pitch {
+        filter = filter.set_pitch(pitch);
+    }
+
+    data.music_sessions
+        .update(guild_id.get(), |session| session.filter = filter)
+        .await;
+
+    if let Some(handler_lock) = data.songbird.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+        if let Some(track) = handler.queue().current() {
+            ctx.defer().await?;
+            let position = match track.get_info().await {
+                Ok(state) => track.data::<TrackMetadata>().position(state.position),
+                Err(_) => Default::default(),
+            };
+            let queue = QueueContext::new(ctx);
+            restart_queue(&mut handler, &queue, position).await;
+        }
+    }
+
+    ctx.send(Valeriyya::reply(format!(
+        "The filter has been set to: {}",
+        filter
+    )))
+    .await?;
+
+    Ok(())
+}
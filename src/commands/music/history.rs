@@ -0,0 +1,101 @@
+use crate::{
+    structs::{HistoryEntry, PlayCount, Video},
+    utils::Valeriyya,
+    Context, Error,
+};
+
+/// How many songs the history and the leaderboard show.
+const SHOWN: usize = 15;
+
+#[doc = "Shows what was played in this guild."]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Music",
+    subcommands("music", "top"),
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn history(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[doc = "Shows the songs that were played last."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn music(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let entries = HistoryEntry::recent(&ctx.data().database(), guild_id.get(), SHOWN).await?;
+
+    if entries.is_empty() {
+        ctx.send(Valeriyya::reply("No songs were played yet.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let description = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let video = Video::from(entry.track.clone());
+            format!(
+                "`{}.` [{}]({}) - <@{}> {}",
+                i + 1,
+                video.title,
+                video.url(),
+                entry.requester,
+                Valeriyya::time_format((entry.played_at.timestamp_millis() / 1000).to_string())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        Valeriyya::reply_default().embed(
+            Valeriyya::embed()
+                .title("Recently played")
+                .description(description),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Shows the songs played the most in this guild."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn top(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let counts = PlayCount::top(&ctx.data().database(), guild_id.get(), SHOWN).await?;
+
+    if counts.is_empty() {
+        ctx.send(Valeriyya::reply("No songs were played yet.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let description = counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let video = Video::from(count.track.clone());
+            format!(
+                "`{}.` [{}]({}) `{} plays`",
+                i + 1,
+                video.title,
+                video.url(),
+                count.plays
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        Valeriyya::reply_default().embed(
+            Valeriyya::embed()
+                .title("Most played")
+                .description(description),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -26,6 +26,9 @@ pub async fn join(ctx: Context<'_>) -> Result<(), Error> {
     };
 
     let _ = ctx.data().songbird.join(guild_id, connect_to).await;
+    ctx.data()
+        .inactivity
+        .watch(ctx.serenity_context(), guild_id);
 
     ctx.send(Valeriyya::reply("I have joined your channel"))
         .await?;
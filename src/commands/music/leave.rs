@@ -1,17 +1,24 @@
-use crate::{utils::Valeriyya, Context, Error};
+use crate::{
+    structs::SavedQueue,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
 
 #[doc = "Leaves the voice channel."]
 #[poise::command(
     prefix_command,
     slash_command,
     default_member_permissions = "VIEW_CHANNEL",
-    category = "Music"
+    category = "Music",
+    check = "dj_check"
 )]
 pub async fn leave(ctx: Context<'_>) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
     if ctx.data().songbird.get(guild_id).is_some() {
         let _ = ctx.data().songbird.remove(guild_id).await;
+        ctx.data().music_sessions.remove(guild_id.get()).await;
+        let _ = SavedQueue::delete(&ctx.data(), guild_id.get()).await;
 
         ctx.send(
             Valeriyya::reply_default().embed(
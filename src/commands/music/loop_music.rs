@@ -1,51 +1,86 @@
-use crate::{utils::Valeriyya, Context, Error};
+use crate::{
+    structs::LoopMode,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
 
-#[doc = "Puts the current song on repeat."]
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum LoopChoice {
+    #[name = "off"]
+    Off,
+    #[name = "track"]
+    Track,
+    #[name = "queue"]
+    Queue,
+}
+
+#[doc = "Changes how the current song or the queue repeats."]
 #[poise::command(
     prefix_command,
     slash_command,
     default_member_permissions = "VIEW_CHANNEL",
     rename = "loop",
-    category = "Music"
+    category = "Music",
+    check = "dj_check"
 )]
 pub async fn loop_music(
     ctx: Context<'_>,
-    #[description = "Change the loop mode."]
-    #[rename = "loop"]
-    loop_bool: bool,
+    #[description = "Change the loop mode."] mode: LoopChoice,
+    #[description = "How many more times the current song should repeat."]
+    #[min = 1]
+    times: Option<usize>,
 ) -> Result<(), Error> {
     let guild_id = ctx.guild_id().unwrap();
 
-    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        if handler.queue().current().is_some() {
-            if !loop_bool {
-                handler.queue().current().unwrap().disable_loop()?;
-            }
-            handler.queue().current().unwrap().enable_loop()?;
-
-            ctx.send(
-                Valeriyya::reply_default().embed(
-                    Valeriyya::embed()
-                        .description(format!(
-                            "Loop {}",
-                            if loop_bool { "enabled" } else { "disabled" }
-                        ))
-                        .title("Loop information"),
-                ),
-            )
-            .await?;
-        } else {
-            ctx.send(
-                Valeriyya::reply_default().embed(
-                    Valeriyya::embed()
-                        .description("There is no songs in the queue.")
-                        .title("Loop information"),
-                ),
-            )
-            .await?;
+    let current = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
+    };
+
+    let Some(current) = current else {
+        ctx.send(
+            Valeriyya::reply_default().embed(
+                Valeriyya::embed()
+                    .description("There is no songs in the queue.")
+                    .title("Loop information"),
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let loop_mode = match (mode, times) {
+        (LoopChoice::Off, _) => {
+            current.disable_loop()?;
+            LoopMode::Off
+        }
+        (LoopChoice::Track, Some(times)) => {
+            current.loop_for(times)?;
+            LoopMode::TrackTimes(times)
+        }
+        (LoopChoice::Track, None) => {
+            current.enable_loop()?;
+            LoopMode::Track
+        }
+        (LoopChoice::Queue, _) => {
+            current.disable_loop()?;
+            LoopMode::Queue
         }
     };
 
+    ctx.data()
+        .music_sessions
+        .update(guild_id.get(), |session| session.loop_mode = loop_mode)
+        .await;
+
+    ctx.send(
+        Valeriyya::reply_default().embed(
+            Valeriyya::embed()
+                .description(format!("Loop mode set to {}.", loop_mode))
+                .title("Loop information"),
+        ),
+    )
+    .await?;
+
     Ok(())
 }
@@ -0,0 +1,271 @@
+use std::time::{Duration, Instant};
+
+use serenity::all::{
+    ButtonStyle, ComponentInteractionCollector, CreateActionRow, CreateButton, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use songbird::tracks::{PlayMode, TrackHandle};
+
+use crate::{
+    lyrics::{clean_title, Lyrics},
+    structs::TrackMetadata,
+    utils::Valeriyya,
+    Context, Error,
+};
+
+/// The most characters of lyrics an embed page shows.
+const PAGE_LENGTH: usize = 2000;
+/// How many synced lines are shown before and after the current one.
+const SYNCED_CONTEXT: usize = 4;
+const SYNCED_REFRESH: Duration = Duration::from_secs(3);
+/// How long synced lyrics keep following, in case the song stays paused.
+const SYNCED_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[doc = "Shows the lyrics of the current song or of a song you look up."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn lyrics(
+    ctx: Context<'_>,
+    #[description = "Highlight the line being sung."] synced: Option<bool>,
+    #[description = "The song to look up, the current song by default."]
+    #[rest]
+    query: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let current = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
+    };
+
+    let looked_up = query.is_some();
+    let query = match (query, &current) {
+        (Some(query), _) => query,
+        (None, Some(track)) => clean_title(&track.data::<TrackMetadata>().video.title),
+        (None, None) => {
+            ctx.send(
+                Valeriyya::reply(
+                    "There is nothing playing right now, which song should I look up?",
+                )
+                .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.defer().await?;
+    let lyrics = match ctx.data().lyrics.find(&query).await {
+        Ok(lyrics) => lyrics,
+        Err(error) => {
+            ctx.send(Valeriyya::reply(error.to_string()).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Falls back to the plain lyrics, saying why.
+    let note = match current {
+        _ if !synced.unwrap_or(false) => "",
+        Some(_) if looked_up => "Synced lyrics only follow the current song.",
+        Some(track) if lyrics.synced.is_some() => {
+            return follow_lyrics(ctx, &lyrics, track).await;
+        }
+        Some(_) => "There are no synced lyrics for this song.",
+        None => "Synced lyrics follow the current song, but nothing is playing.",
+    };
+
+    paged_lyrics(ctx, &lyrics, note).await
+}
+
+/// Splits the lyrics on line breaks into pages that fit an embed.
+fn pages(text: &str) -> Vec<String> {
+    let mut pages = vec![String::new()];
+    for line in text.lines() {
+        let page = pages.last_mut().unwrap();
+        if !page.is_empty() && page.len() + line.len() + 1 > PAGE_LENGTH {
+            pages.push(String::new());
+        }
+
+        let page = pages.last_mut().unwrap();
+        page.push_str(&Valeriyya::truncate(line, PAGE_LENGTH - 1));
+        page.push('\n');
+    }
+    pages
+}
+
+async fn paged_lyrics(ctx: Context<'_>, lyrics: &Lyrics, note: &str) -> Result<(), Error> {
+    let pages = pages(&lyrics.plain);
+    let mut page = 0;
+
+    let ctx_id = ctx.id().to_string();
+    let prev_id = format!("{}lyrics_prev", ctx_id);
+    let next_id = format!("{}lyrics_next", ctx_id);
+    let buttons = vec![CreateActionRow::Buttons(
+        vec![
+            CreateButton::new(prev_id.clone())
+                .style(ButtonStyle::Secondary)
+                .label("Previous"),
+            CreateButton::new(next_id.clone())
+                .style(ButtonStyle::Secondary)
+                .label("Next"),
+        ]
+        .into(),
+    )];
+
+    let mut reply = Valeriyya::reply(note).embed(lyrics_embed(lyrics, &pages, page));
+    if pages.len() > 1 {
+        reply = reply.components(buttons);
+    }
+    ctx.send(reply).await?;
+
+    if pages.len() == 1 {
+        return Ok(());
+    }
+
+    while let Some(mci) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(120))
+        .filter({
+            let ctx_id = ctx_id.clone();
+            move |mci| mci.data.custom_id.starts_with(&ctx_id)
+        })
+        .await
+    {
+        if *mci.data.custom_id == *prev_id {
+            page = page.checked_sub(1).unwrap_or(pages.len() - 1);
+        } else if *mci.data.custom_id == *next_id {
+            page = (page + 1) % pages.len();
+        } else {
+            continue;
+        }
+
+        mci.create_response(
+            ctx.http(),
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().embed(lyrics_embed(lyrics, &pages, page)),
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn lyrics_embed<'a>(lyrics: &Lyrics, pages: &[String], page: usize) -> CreateEmbed<'a> {
+    Valeriyya::embed()
+        .title(format!("{} - {}", lyrics.artist, lyrics.title))
+        .description(pages[page].clone())
+        .footer(Valeriyya::reply_footer(format!(
+            "Page {}/{}",
+            page + 1,
+            pages.len()
+        )))
+}
+
+/// Keeps the message on the line being sung until the song ends or is skipped.
+async fn follow_lyrics(ctx: Context<'_>, lyrics: &Lyrics, track: TrackHandle) -> Result<(), Error> {
+    let metadata = track.data::<TrackMetadata>();
+
+    let Ok(state) = track.get_info().await else {
+        ctx.send(Valeriyya::reply("There is nothing playing right now.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+    let reply = ctx
+        .send(Valeriyya::reply("").embed(synced_embed(lyrics, metadata.position(state.position))))
+        .await?;
+
+    let started = Instant::now();
+    let mut interval = tokio::time::interval(SYNCED_REFRESH);
+    interval.tick().await;
+
+    while started.elapsed() < SYNCED_TIMEOUT {
+        interval.tick().await;
+        // The handle errors once the track is gone.
+        let Ok(state) = track.get_info().await else {
+            break;
+        };
+        if state.playing.is_done() {
+            break;
+        }
+        if state.playing == PlayMode::Pause {
+            continue;
+        }
+
+        reply
+            .edit(
+                ctx,
+                Valeriyya::reply("").embed(synced_embed(lyrics, metadata.position(state.position))),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn synced_embed<'a>(lyrics: &Lyrics, position: Duration) -> CreateEmbed<'a> {
+    let lines = lyrics.synced.as_deref().unwrap_or_default();
+    let current = lyrics.current_line(position);
+    let center = current.unwrap_or(0);
+
+    let description = lines
+        .iter()
+        .enumerate()
+        .skip(center.saturating_sub(SYNCED_CONTEXT))
+        .take(SYNCED_CONTEXT * 2 + 1)
+        .map(|(i, line)| {
+            let text = if line.text.is_empty() {
+                "♪"
+            } else {
+                line.text.as_str()
+            };
+            if Some(i) == current {
+                format!("**▶ {}**", text)
+            } else {
+                text.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Valeriyya::embed()
+        .title(format!("{} - {}", lyrics.artist, lyrics.title))
+        .description(description)
+        .footer(Valeriyya::reply_footer(Valeriyya::format_duration(
+            position,
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_keep_short_lyrics_together() {
+        assert_eq!(pages("One\nTwo"), ["One\nTwo\n"]);
+    }
+
+    #[test]
+    fn pages_split_on_line_breaks() {
+        let text = vec!["a".repeat(9); 300].join("\n");
+        let pages = pages(&text);
+
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|page| page.len() <= PAGE_LENGTH));
+        assert_eq!(pages[0].lines().count(), 200);
+        assert_eq!(pages[1].lines().count(), 100);
+    }
+
+    #[test]
+    fn pages_truncate_overlong_lines() {
+        let pages = pages(&"a".repeat(PAGE_LENGTH * 2));
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].chars().count(), PAGE_LENGTH);
+    }
+}
@@ -1,3 +1,7 @@
 use crate::import;
 
-import![play, skip, leave, join, loop_music];
+import![
+    play, skip, leave, join, loop_music, queue, remove, move_track, shuffle, clear, skipto,
+    nowplaying, pause, resume, stop, seek, volume, search, playlist, filter, lyrics, history,
+    previous
+];
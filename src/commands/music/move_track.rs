@@ -0,0 +1,57 @@
+use crate::{
+    structs::TrackMetadata,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Moves a song to another position in the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    rename = "move",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn move_track(
+    ctx: Context<'_>,
+    #[description = "The current position of the song."]
+    #[min = 1]
+    from: usize,
+    #[description = "The new position of the song."]
+    #[min = 1]
+    to: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let moved = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().modify_queue(|queue| {
+            if from == 0 || to == 0 || from >= queue.len() || to >= queue.len() {
+                return None;
+            }
+            let track = queue.remove(from)?;
+            let metadata = track.data::<TrackMetadata>();
+            queue.insert(to, track);
+            Some(metadata)
+        }),
+        None => None,
+    };
+
+    match moved {
+        Some(metadata) => {
+            ctx.send(Valeriyya::reply(format!(
+                "Moved [{}]({}) to position {}.",
+                metadata.video.title,
+                metadata.video.url(),
+                to
+            )))
+            .await?;
+        }
+        None => {
+            ctx.send(Valeriyya::reply("Those positions are not in the queue.").ephemeral(true))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, ComponentInteractionCollector, CreateActionRow,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use songbird::{
+    tracks::{PlayMode, TrackHandle, TrackState},
+    Call,
+};
+use tokio::sync::Mutex;
+
+use super::skip::{request_skip, SkipRequest};
+use crate::{
+    structs::{LoopMode, TrackMetadata},
+    utils::{can_control_playback, Valeriyya},
+    Context, Error,
+};
+
+const PROGRESS_BAR_LENGTH: usize = 20;
+
+#[doc = "Shows the song that is currently playing."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    aliases("np"),
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn nowplaying(
+    ctx: Context<'_>,
+    #[description = "Keep updating the message while the queue plays."] live: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let handler_lock = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            ctx.send(Valeriyya::reply("There is nothing playing right now.").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let playing = match current_track(&handler_lock).await {
+        Some(track) => track.get_info().await.ok().map(|state| (track, state)),
+        None => None,
+    };
+
+    let (mut track, mut state) = match playing {
+        Some(playing) => playing,
+        None => {
+            ctx.send(Valeriyya::reply("There is nothing playing right now.").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let ctx_id = ctx.id().to_string();
+    let pause_id = format!("{}np_pause", ctx_id);
+    let skip_id = format!("{}np_skip", ctx_id);
+    let controls = |paused: bool| {
+        vec![CreateActionRow::Buttons(
+            vec![
+                CreateButton::new(pause_id.clone())
+                    .style(ButtonStyle::Primary)
+                    .label(if paused { "Resume" } else { "Pause" }),
+                CreateButton::new(skip_id.clone())
+                    .style(ButtonStyle::Secondary)
+                    .label("Skip"),
+            ]
+            .into(),
+        )]
+    };
+
+    let reply = ctx
+        .send(
+            Valeriyya::reply("")
+                .embed(now_playing_embed(&track, &state, loop_mode(ctx).await))
+                .components(controls(state.playing == PlayMode::Pause)),
+        )
+        .await?;
+
+    let mut interactions = Box::pin(
+        ComponentInteractionCollector::new(ctx.serenity_context())
+            .channel_id(ctx.channel_id())
+            .timeout(Duration::from_secs(600))
+            .filter(move |mci| mci.data.custom_id.starts_with(&ctx_id))
+            .stream(),
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    interval.tick().await;
+
+    loop {
+        tokio::select! {
+            mci = interactions.next() => {
+                let Some(mci) = mci else { break };
+                let Some(current) = current_track(&handler_lock).await else { break };
+
+                if *mci.data.custom_id == *pause_id {
+                    if !can_control_playback(ctx, mci.user.id).await? {
+                        let message = "Only a DJ can pause or resume the song.";
+                        respond(ctx, &mci, message.to_string(), true).await?;
+                        continue;
+                    }
+                    match current.get_info().await.map(|state| state.playing) {
+                        Ok(PlayMode::Pause) => { let _ = current.play(); }
+                        _ => { let _ = current.pause(); }
+                    }
+                } else if *mci.data.custom_id == *skip_id {
+                    // The button votes like `/skip` does, unless the presser can skip outright.
+                    match request_skip(ctx, &mci.user).await? {
+                        SkipRequest::Skipped(_) => {}
+                        SkipRequest::Voted(message) => {
+                            respond(ctx, &mci, message, false).await?;
+                            continue;
+                        }
+                        SkipRequest::Denied(message) => {
+                            respond(ctx, &mci, message, true).await?;
+                            continue;
+                        }
+                    }
+                }
+
+                // Give the driver a moment to apply the action before rendering it.
+                tokio::time::sleep(Duration::from_millis(250)).await;
+                let Some(current) = current_track(&handler_lock).await else { break };
+                let Ok(current_state) = current.get_info().await else { break };
+                (track, state) = (current, current_state);
+
+                mci.create_response(
+                    ctx.http(),
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .embed(now_playing_embed(&track, &state, loop_mode(ctx).await))
+                            .components(controls(state.playing == PlayMode::Pause)),
+                    ),
+                )
+                .await?;
+            }
+            _ = interval.tick(), if live.unwrap_or(false) => {
+                let Some(current) = current_track(&handler_lock).await else { break };
+                let Ok(current_state) = current.get_info().await else { break };
+                (track, state) = (current, current_state);
+
+                reply
+                    .edit(
+                        ctx,
+                        Valeriyya::reply("")
+                            .embed(now_playing_embed(&track, &state, loop_mode(ctx).await))
+                            .components(controls(state.playing == PlayMode::Pause)),
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    reply
+        .edit(
+            ctx,
+            Valeriyya::reply("")
+                .embed(now_playing_embed(&track, &state, loop_mode(ctx).await))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn respond(
+    ctx: Context<'_>,
+    mci: &ComponentInteraction,
+    message: String,
+    ephemeral: bool,
+) -> Result<(), Error> {
+    mci.create_response(
+        ctx.http(),
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(message)
+                .ephemeral(ephemeral),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn current_track(handler_lock: &Arc<Mutex<Call>>) -> Option<TrackHandle> {
+    handler_lock.lock().await.queue().current()
+}
+
+async fn loop_mode(ctx: Context<'_>) -> LoopMode {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data().music_sessions.get(guild_id).await.loop_mode
+}
+
+fn progress_bar(position: Duration, duration: Duration) -> String {
+    let progress = if duration.is_zero() {
+        0
+    } else {
+        (position.as_secs_f64() / duration.as_secs_f64() * PROGRESS_BAR_LENGTH as f64) as usize
+    };
+
+    (0..PROGRESS_BAR_LENGTH)
+        .map(|i| {
+            if i == progress.min(PROGRESS_BAR_LENGTH - 1) {
+                "🔘"
+            } else {
+                "▬"
+            }
+        })
+        .collect()
+}
+
+fn now_playing_embed<'a>(
+    track: &TrackHandle,
+    state: &TrackState,
+    loop_mode: LoopMode,
+) -> CreateEmbed<'a> {
+    let metadata = track.data::<TrackMetadata>();
+    let position = metadata.position(state.position);
+
+    let embed = Valeriyya::embed()
+        .title(if state.playing == PlayMode::Pause {
+            "Paused"
+        } else {
+            "Now playing"
+        })
+        .description(format!(
+            "[{}]({})\n\n{} `{}/{}`\n\nRequested by <@{}> • Loop: {}",
+            metadata.video.title,
+            metadata.video.url(),
+            progress_bar(position, metadata.video.duration),
+            Valeriyya::format_duration(position),
+            Valeriyya::format_duration(metadata.video.duration),
+            metadata.requester,
+            loop_mode
+        ));
+
+    match metadata.video.thumbnail() {
+        Some(thumbnail) => embed.thumbnail(thumbnail),
+        None => embed,
+    }
+}
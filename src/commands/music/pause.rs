@@ -0,0 +1,31 @@
+use crate::{
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Pauses the currently playing song."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn pause(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        if handler.queue().pause().is_ok() {
+            drop(handler);
+            ctx.send(Valeriyya::reply("The song has been paused."))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    ctx.send(Valeriyya::reply("There is nothing playing right now.").ephemeral(true))
+        .await?;
+
+    Ok(())
+}
@@ -1,13 +1,37 @@
+use poise::serenity_prelude::{Attachment, AutocompleteChoice};
+
 use crate::{
-    structs::{SongEndNotifier, SongPlayNotifier, Video},
-    utils::Valeriyya,
+    utils::{play_resolved, Valeriyya},
     Context, Error,
 };
 
-use songbird::{input::YoutubeDl, Event, TrackEvent};
-use std::time::Duration;
+/// How many search results are suggested while the query is typed.
+const SUGGESTIONS: usize = 5;
+
+/// Suggests search results while a query is typed, links are left as they are. Runs on every
+/// keystroke, so it never goes through the Data API.
+async fn autocomplete_url<'a>(ctx: Context<'_>, partial: &'a str) -> Vec<AutocompleteChoice<'a>> {
+    let partial = partial.trim();
+    if partial.chars().count() < 3 || partial.starts_with("http") || partial.starts_with("spotify:")
+    {
+        return Vec::new();
+    }
+
+    Valeriyya::suggest(ctx, partial, SUGGESTIONS)
+        .await
+        .into_iter()
+        .map(|video| {
+            let name = format!(
+                "{} ({})",
+                video.title,
+                Valeriyya::format_duration(video.duration)
+            );
+            AutocompleteChoice::new(Valeriyya::truncate(&name, 100), video.url())
+        })
+        .collect()
+}
 
-#[doc = "Plays a song."]
+#[doc = "Plays a song, a link to an audio file or stream, or an attached audio file."]
 #[poise::command(
     prefix_command,
     slash_command,
@@ -18,20 +42,20 @@ use std::time::Duration;
 pub async fn play(
     ctx: Context<'_>,
     #[description = "The url of the song or playlist"]
+    #[autocomplete = "autocomplete_url"]
     #[rest]
-    url: String,
+    url: Option<String>,
+    #[description = "An audio file to play"] file: Option<Attachment>,
 ) -> Result<(), Error> {
-    let request_client = reqwest::Client::new();
-    let guild_id = ctx.guild_id().unwrap();
-
-    let channel_id = ctx
-        .guild()
-        .unwrap()
-        .voice_states
-        .get(&ctx.author().id)
-        .and_then(|voice_state| voice_state.channel_id);
+    let Some(url) = file.map(|file| file.url.to_string()).or(url) else {
+        ctx.send(
+            Valeriyya::reply("Give me a song to play or attach an audio file.").ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
 
-    let connect_to = match channel_id {
+    let connect_to = match Valeriyya::author_voice_channel(ctx) {
         Some(channel) => channel,
         None => {
             ctx.send(Valeriyya::reply("You are not in a voice channel..").ephemeral(true))
@@ -41,87 +65,33 @@ pub async fn play(
     };
 
     let msg = ctx.say("Loading song...").await?;
-    let _ = ctx.data().songbird.join(guild_id, connect_to).await;
-
-    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-
-        // --- Only one call, always returns Vec<Video> ---
-        let metadata: Vec<Video> = Valeriyya::get_metadata(ctx, url.clone()).await;
-
-        println!("fadasdasdasd");
-        println!("{:?}", metadata);
-
-        // Prepare sources for all videos
-        let sources: Vec<YoutubeDl> = metadata
-            .iter()
-            .map(|video| YoutubeDl::new(request_client.clone(), video.id.clone()))
-            .collect();
-
-        for (i, source) in sources.into_iter().enumerate() {
-            let queue = handler.enqueue_with_preload(
-                source.into(),
-                Some(metadata[i].duration - Duration::from_secs(15)),
-            );
-            let _ = queue.add_event(
-                Event::Track(TrackEvent::End),
-                SongEndNotifier {
-                    channel_id: ctx.channel_id().expect_channel(),
-                    http: ctx.serenity_context().http.clone(),
-                    metadata: metadata[i].clone(),
-                },
-            );
 
-            if metadata.len() >= 2 && i >= 1 {
-                let _ = queue.add_event(
-                    Event::Track(TrackEvent::Play),
-                    SongPlayNotifier {
-                        channel_id: ctx.channel_id().expect_channel(),
-                        http: ctx.serenity_context().http.clone(),
-                        metadata: metadata[i].clone(),
-                    },
-                );
-            }
+    let resolved = match Valeriyya::get_metadata(ctx, url.clone()).await {
+        Ok(resolved) => resolved,
+        Err(error) => {
+            msg.edit(
+                ctx,
+                Valeriyya::reply("").embed(
+                    Valeriyya::embed()
+                        .description(error.to_string())
+                        .title("Song information"),
+                ),
+            )
+            .await?;
+            return Ok(());
         }
-
-        let queue_clone = handler.queue().clone();
-        let mng = ctx.data().songbird.clone();
-
-        tokio::task::spawn(async move {
-            let queue = queue_clone;
-            loop {
-                if !queue.is_empty() {
-                    tokio::time::sleep(Duration::from_secs(600)).await;
-                    continue;
-                }
-                let _ = mng.remove(guild_id).await;
-                break;
-            }
-        });
-
-        let information_title = if metadata.len() > 1 {
-            "Queued"
-        } else {
-            "Playing"
-        };
-
-        msg.edit(
-            ctx,
-            Valeriyya::reply("").embed(
-                Valeriyya::embed()
-                    .description(format!(
-                        "{} [{}]({})",
-                        information_title,
-                        metadata[0].title,
-                        format_args!("https://youtu.be/{}", metadata[0].id)
-                    ))
-                    .title("Song information"),
-            ),
-        )
-        .await?;
-
-        drop(handler);
     };
 
+    let description = play_resolved(ctx, connect_to, resolved).await?;
+    msg.edit(
+        ctx,
+        Valeriyya::reply("").embed(
+            Valeriyya::embed()
+                .description(description)
+                .title("Song information"),
+        ),
+    )
+    .await?;
+
     Ok(())
 }
\ No newline at end of file
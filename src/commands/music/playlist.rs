@@ -0,0 +1,339 @@
+use crate::{
+    resolver::Resolved,
+    structs::{PlaylistOwner, PlaylistTrack, SavedPlaylist, TrackMetadata, MAX_PLAYLIST_TRACKS},
+    utils::{is_dj, play_resolved, Valeriyya},
+    Context, Error,
+};
+
+#[derive(poise::ChoiceParameter, Debug, Clone, Copy, Default)]
+pub enum PlaylistScope {
+    #[default]
+    #[name = "personal"]
+    Personal,
+    #[name = "server"]
+    Server,
+}
+
+#[doc = "Saves and loads playlists."]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    category = "Music",
+    subcommands("save", "load", "list", "delete", "add", "remove"),
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn playlist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+fn playlist_owner(ctx: Context<'_>, scope: Option<PlaylistScope>) -> PlaylistOwner {
+    match scope.unwrap_or_default() {
+        PlaylistScope::Personal => PlaylistOwner::User(ctx.author().id),
+        PlaylistScope::Server => PlaylistOwner::Guild(ctx.guild_id().unwrap()),
+    }
+}
+
+/// Personal playlists are always editable by their owner, server playlists only by DJs.
+async fn can_edit(ctx: Context<'_>, owner: PlaylistOwner) -> Result<bool, Error> {
+    if let PlaylistOwner::Guild(_) = owner {
+        if !is_dj(ctx).await? {
+            ctx.send(
+                Valeriyya::reply("Only DJs can change the server's playlists.").ephemeral(true),
+            )
+            .await?;
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+async fn find_playlist(
+    ctx: Context<'_>,
+    owner: PlaylistOwner,
+    name: &str,
+) -> Result<Option<SavedPlaylist>, Error> {
+    let playlist = SavedPlaylist::find(&ctx.data(), owner, name).await?;
+    if playlist.is_none() {
+        ctx.send(
+            Valeriyya::reply(format!("There is no playlist named `{}`.", name)).ephemeral(true),
+        )
+        .await?;
+    }
+    Ok(playlist)
+}
+
+#[doc = "Saves the queue as a playlist, replacing a playlist with the same name."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn save(
+    ctx: Context<'_>,
+    #[description = "The name of the playlist."] name: String,
+    #[description = "Whether it is your playlist or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let owner = playlist_owner(ctx, scope);
+    if !can_edit(ctx, owner).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let tracks: Vec<PlaylistTrack> = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock
+            .lock()
+            .await
+            .queue()
+            .current_queue()
+            .iter()
+            .take(MAX_PLAYLIST_TRACKS)
+            .map(|track| PlaylistTrack::from(&track.data::<TrackMetadata>().video))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    if tracks.is_empty() {
+        ctx.send(Valeriyya::reply("There is no songs in the queue!").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let mut playlist = SavedPlaylist::new(owner, &name);
+    playlist.tracks = tracks;
+    playlist.save(&ctx.data()).await?;
+
+    ctx.say(format!(
+        "Saved {} songs to the playlist `{}`.",
+        playlist.tracks.len(),
+        name
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Adds the songs of a playlist to the queue."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn load(
+    ctx: Context<'_>,
+    #[description = "The name of the playlist."] name: String,
+    #[description = "Whether it is your playlist or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let connect_to = match Valeriyya::author_voice_channel(ctx) {
+        Some(channel) => channel,
+        None => {
+            ctx.send(Valeriyya::reply("You are not in a voice channel..").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let Some(playlist) = find_playlist(ctx, playlist_owner(ctx, scope), &name).await? else {
+        return Ok(());
+    };
+
+    if playlist.tracks.is_empty() {
+        ctx.send(Valeriyya::reply(format!("The playlist `{}` is empty.", name)).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    let videos = playlist.videos();
+    if videos.is_empty() {
+        ctx.send(
+            Valeriyya::reply(format!(
+                "None of the songs in the playlist `{}` can be played.",
+                name
+            ))
+            .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let msg = ctx.say("Loading playlist...").await?;
+
+    let resolved = Resolved {
+        skipped: playlist.tracks.len() - videos.len(),
+        tracks: videos,
+        pending: None,
+    };
+    let description = play_resolved(ctx, connect_to, resolved).await?;
+    msg.edit(
+        ctx,
+        Valeriyya::reply("").embed(
+            Valeriyya::embed()
+                .description(description)
+                .title(format!("Playlist {}", playlist.name)),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Lists the saved playlists."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Whether to list yours or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let owner = playlist_owner(ctx, scope);
+    let playlists = SavedPlaylist::list(&ctx.data(), owner).await?;
+
+    if playlists.is_empty() {
+        ctx.send(Valeriyya::reply("There are no saved playlists.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let description = playlists
+        .iter()
+        .map(|playlist| {
+            format!(
+                "`{}` - {} songs `{}`",
+                playlist.name,
+                playlist.tracks.len(),
+                Valeriyya::format_duration(playlist.duration())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let title = match owner {
+        PlaylistOwner::User(_) => "Your playlists",
+        PlaylistOwner::Guild(_) => "Server playlists",
+    };
+
+    ctx.send(
+        Valeriyya::reply_default().embed(Valeriyya::embed().title(title).description(description)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Deletes a playlist."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "The name of the playlist."] name: String,
+    #[description = "Whether it is your playlist or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let owner = playlist_owner(ctx, scope);
+    if !can_edit(ctx, owner).await? {
+        return Ok(());
+    }
+
+    if SavedPlaylist::delete(&ctx.data(), owner, &name).await? {
+        ctx.say(format!("Deleted the playlist `{}`.", name)).await?;
+    } else {
+        ctx.send(
+            Valeriyya::reply(format!("There is no playlist named `{}`.", name)).ephemeral(true),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[doc = "Adds songs to a playlist, creating it if it doesn't exist."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The name of the playlist."] name: String,
+    #[description = "The url of the song or playlist to add."] url: String,
+    #[description = "Whether it is your playlist or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let owner = playlist_owner(ctx, scope);
+    if !can_edit(ctx, owner).await? {
+        return Ok(());
+    }
+
+    let msg = ctx.say("Loading song...").await?;
+
+    let resolved = match Valeriyya::get_metadata(ctx, url).await {
+        Ok(resolved) => resolved,
+        Err(error) => {
+            msg.edit(ctx, Valeriyya::reply(error.to_string())).await?;
+            return Ok(());
+        }
+    };
+
+    let mut videos = resolved.tracks;
+    let mut skipped = resolved.skipped;
+    if let Some(mut pending) = resolved.pending {
+        while let Some(batch) = pending.receiver.recv().await {
+            videos.extend(batch);
+        }
+        skipped += pending.skipped.await.unwrap_or(0);
+    }
+
+    let data = &ctx.data();
+    let mut playlist = SavedPlaylist::find(data, owner, &name)
+        .await?
+        .unwrap_or_else(|| SavedPlaylist::new(owner, &name));
+
+    let space = MAX_PLAYLIST_TRACKS.saturating_sub(playlist.tracks.len());
+    let added = videos.len().min(space);
+    playlist
+        .tracks
+        .extend(videos.iter().take(added).map(PlaylistTrack::from));
+    playlist.save(data).await?;
+
+    let mut reply = format!("Added {} songs to the playlist `{}`.", added, name);
+    if added < videos.len() {
+        reply.push_str(&format!(
+            "\nPlaylists can hold up to {} songs, the rest were left out.",
+            MAX_PLAYLIST_TRACKS
+        ));
+    }
+    if skipped > 0 {
+        reply.push_str(&format!(
+            "\nSkipped {} private, deleted or unavailable songs.",
+            skipped
+        ));
+    }
+    msg.edit(ctx, Valeriyya::reply(reply)).await?;
+
+    Ok(())
+}
+
+#[doc = "Removes a song from a playlist."]
+#[poise::command(slash_command, prefix_command, category = "Music")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The name of the playlist."] name: String,
+    #[description = "The position of the song in the playlist."]
+    #[min = 1]
+    position: usize,
+    #[description = "Whether it is your playlist or the server's."] scope: Option<PlaylistScope>,
+) -> Result<(), Error> {
+    let owner = playlist_owner(ctx, scope);
+    if !can_edit(ctx, owner).await? {
+        return Ok(());
+    }
+
+    let Some(mut playlist) = find_playlist(ctx, owner, &name).await? else {
+        return Ok(());
+    };
+
+    if position == 0 || position > playlist.tracks.len() {
+        ctx.send(
+            Valeriyya::reply(format!(
+                "The playlist `{}` only has {} songs.",
+                name,
+                playlist.tracks.len()
+            ))
+            .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let track = playlist.tracks.remove(position - 1);
+    playlist.save(&ctx.data()).await?;
+
+    ctx.say(format!(
+        "Removed `{}` from the playlist `{}`.",
+        track.title, name
+    ))
+    .await?;
+
+    Ok(())
+}
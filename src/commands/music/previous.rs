@@ -0,0 +1,123 @@
+use crate::{
+    structs::{HistoryEntry, QueueContext, TrackMetadata, Video},
+    utils::{dj_check, enqueue_video, play_resolved, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Plays the song that was played before the current one."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    aliases("prev", "back"),
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn previous(ctx: Context<'_>) -> Result<(), Error> {
+    let connect_to = match Valeriyya::author_voice_channel(ctx) {
+        Some(channel) => channel,
+        None => {
+            ctx.send(Valeriyya::reply("You are not in a voice channel..").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    // The history is kept as it is, the session remembers how far back `previous` went.
+    let cursor = data.music_sessions.get(guild_id.get()).await.history_cursor;
+    let Some(entry) = HistoryEntry::before(&data.database(), guild_id.get(), cursor).await? else {
+        ctx.send(Valeriyya::reply("No songs were played before this one.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+    data.music_sessions
+        .update(guild_id.get(), |session| {
+            session.history_cursor = Some(entry.played_at)
+        })
+        .await;
+    if !entry.track.is_playable() {
+        ctx.send(Valeriyya::reply("The previous song can't be played anymore.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    let video = Video::from(entry.track);
+
+    let current = match data.songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
+    };
+
+    let Some(current) = current else {
+        let description = play_resolved(ctx, connect_to, vec![video].into()).await?;
+        ctx.send(
+            Valeriyya::reply("").embed(
+                Valeriyya::embed()
+                    .description(description)
+                    .title("Song information"),
+            ),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    // Like `play`, the bot follows the author into their voice channel.
+    let Ok(handler_lock) = data.songbird.join(guild_id, connect_to).await else {
+        ctx.send(Valeriyya::reply("I could not join your voice channel.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+
+    let volume = Valeriyya::get_database(data, guild_id.get())
+        .await?
+        .music
+        .track_volume();
+    let filter = data.music_sessions.get(guild_id.get()).await.filter;
+    let queue = QueueContext::new(ctx);
+
+    // The previous song plays right away and the current one starts over after it.
+    let mut handler = handler_lock.lock().await;
+    let playing = current.data::<TrackMetadata>();
+    enqueue_video(
+        &mut handler,
+        &queue,
+        TrackMetadata::new(video.clone(), ctx.author().id),
+        volume,
+        filter,
+        false,
+    );
+    enqueue_video(
+        &mut handler,
+        &queue,
+        TrackMetadata::new(playing.video.clone(), playing.requester),
+        volume,
+        filter,
+        true,
+    );
+    handler.queue().modify_queue(|queued| {
+        let moved = queued.split_off(queued.len() - 2);
+        for (i, track) in moved.into_iter().enumerate() {
+            queued.insert(1 + i, track);
+        }
+    });
+
+    data.music_sessions
+        .update(guild_id.get(), |session| {
+            session.replaced.insert(current.uuid());
+        })
+        .await;
+    let _ = handler.queue().skip();
+    drop(handler);
+
+    ctx.send(
+        Valeriyya::reply("").embed(
+            Valeriyya::embed()
+                .description(format!("Playing [{}]({})", video.title, video.url()))
+                .title("Song information"),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
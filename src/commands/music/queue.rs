@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, ComponentInteractionCollector, CreateActionRow, CreateButton, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+
+use crate::{structs::TrackMetadata, utils::Valeriyya, Context, Error};
+
+const TRACKS_PER_PAGE: usize = 10;
+
+#[doc = "Shows the songs in the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    aliases("q"),
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn queue(
+    ctx: Context<'_>,
+    #[description = "The page of the queue to show."] page: Option<usize>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let tracks = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current_queue(),
+        None => Vec::new(),
+    };
+
+    if tracks.is_empty() {
+        ctx.send(Valeriyya::reply("There is no songs in the queue!").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let position = tracks[0]
+        .get_info()
+        .await
+        .map(|state| tracks[0].data::<TrackMetadata>().position(state.position))
+        .unwrap_or_default();
+    let metadata: Vec<TrackMetadata> = tracks
+        .iter()
+        .map(|track| track.data::<TrackMetadata>().as_ref().clone())
+        .collect();
+
+    let pages = (metadata.len() - 1).div_ceil(TRACKS_PER_PAGE).max(1);
+    let mut page = page.unwrap_or(1).clamp(1, pages) - 1;
+
+    let ctx_id = ctx.id().to_string();
+    let prev_id = format!("{}queue_prev", ctx_id);
+    let next_id = format!("{}queue_next", ctx_id);
+    let buttons = vec![CreateActionRow::Buttons(
+        vec![
+            CreateButton::new(prev_id.clone())
+                .style(ButtonStyle::Secondary)
+                .label("Previous"),
+            CreateButton::new(next_id.clone())
+                .style(ButtonStyle::Secondary)
+                .label("Next"),
+        ]
+        .into(),
+    )];
+
+    let mut reply = Valeriyya::reply_default().embed(queue_embed(&metadata, position, page, pages));
+    if pages > 1 {
+        reply = reply.components(buttons);
+    }
+    ctx.send(reply).await?;
+
+    if pages == 1 {
+        return Ok(());
+    }
+
+    while let Some(mci) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(120))
+        .filter({
+            let ctx_id = ctx_id.clone();
+            move |mci| mci.data.custom_id.starts_with(&ctx_id)
+        })
+        .await
+    {
+        if *mci.data.custom_id == *prev_id {
+            page = page.checked_sub(1).unwrap_or(pages - 1);
+        } else if *mci.data.custom_id == *next_id {
+            page = (page + 1) % pages;
+        } else {
+            continue;
+        }
+
+        mci.create_response(
+            ctx.http(),
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(queue_embed(&metadata, position, page, pages)),
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+fn queue_embed<'a>(
+    metadata: &[TrackMetadata],
+    position: Duration,
+    page: usize,
+    pages: usize,
+) -> CreateEmbed<'a> {
+    let current = &metadata[0];
+    let remaining = metadata
+        .iter()
+        .map(|track| track.video.duration)
+        .sum::<Duration>()
+        .saturating_sub(position);
+
+    let mut description = format!(
+        "**Now playing:** [{}]({}) `{}/{}` - <@{}>\n",
+        current.video.title,
+        current.video.url(),
+        Valeriyya::format_duration(position),
+        Valeriyya::format_duration(current.video.duration),
+        current.requester
+    );
+
+    if metadata.len() > 1 {
+        description.push_str("\n**Up next:**\n");
+        for (i, track) in metadata
+            .iter()
+            .enumerate()
+            .skip(1 + page * TRACKS_PER_PAGE)
+            .take(TRACKS_PER_PAGE)
+        {
+            description.push_str(&format!(
+                "`{}.` [{}]({}) `{}` - <@{}>\n",
+                i,
+                track.video.title,
+                track.video.url(),
+                Valeriyya::format_duration(track.video.duration),
+                track.requester
+            ));
+        }
+    }
+
+    Valeriyya::embed()
+        .title("Queue")
+        .description(description)
+        .footer(Valeriyya::reply_footer(format!(
+            "Page {}/{} | {} songs | {} remaining",
+            page + 1,
+            pages,
+            metadata.len(),
+            Valeriyya::format_duration(remaining)
+        )))
+}
@@ -0,0 +1,49 @@
+use crate::{
+    structs::TrackMetadata,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Removes a song from the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The position of the song in the queue."]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let removed = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) if position >= 1 => handler_lock.lock().await.queue().dequeue(position),
+        _ => None,
+    };
+
+    match removed {
+        Some(queued) => {
+            let metadata = queued.data::<TrackMetadata>();
+            let _ = queued.stop();
+            ctx.send(Valeriyya::reply(format!(
+                "Removed [{}]({}) from the queue.",
+                metadata.video.title,
+                metadata.video.url()
+            )))
+            .await?;
+        }
+        None => {
+            ctx.send(
+                Valeriyya::reply(format!("There is no song at position {}.", position))
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ComponentInteractionCollector, ComponentInteractionDataKind, CreateActionRow,
+    CreateInteractionResponse, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+};
+
+use crate::{
+    utils::{play_resolved, Valeriyya},
+    Context, Error,
+};
+
+const SEARCH_RESULTS: usize = 10;
+
+#[doc = "Searches for a song and lets you pick which result to play."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL"
+)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search for"]
+    #[rest]
+    query: String,
+) -> Result<(), Error> {
+    let connect_to = match Valeriyya::author_voice_channel(ctx) {
+        Some(channel) => channel,
+        None => {
+            ctx.send(Valeriyya::reply("You are not in a voice channel..").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let msg = ctx.say("Searching...").await?;
+
+    let videos = match Valeriyya::search(ctx, &query, SEARCH_RESULTS).await {
+        Ok(videos) => videos,
+        Err(error) => {
+            msg.edit(
+                ctx,
+                Valeriyya::reply("").embed(
+                    Valeriyya::embed()
+                        .description(error.to_string())
+                        .title("Search results"),
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let description = videos
+        .iter()
+        .enumerate()
+        .map(|(i, video)| {
+            format!(
+                "`{}.` [{}]({}) `{}`",
+                i + 1,
+                video.title,
+                video.url(),
+                Valeriyya::format_duration(video.duration)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let options: Vec<CreateSelectMenuOption> = videos
+        .iter()
+        .enumerate()
+        .map(|(i, video)| {
+            CreateSelectMenuOption::new(Valeriyya::truncate(&video.title, 100), i.to_string())
+                .description(Valeriyya::format_duration(video.duration))
+        })
+        .collect();
+
+    let menu_id = format!("{}search_pick", ctx.id());
+    let menu = CreateSelectMenu::new(
+        menu_id.clone(),
+        CreateSelectMenuKind::String {
+            options: options.into(),
+        },
+    )
+    .placeholder("Pick a song to play");
+
+    msg.edit(
+        ctx,
+        Valeriyya::reply("")
+            .embed(
+                Valeriyya::embed()
+                    .description(description)
+                    .title("Search results"),
+            )
+            .components(vec![CreateActionRow::SelectMenu(menu)]),
+    )
+    .await?;
+
+    let picked = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(60))
+        .filter(move |mci| *mci.data.custom_id == *menu_id)
+        .await;
+
+    let video = picked.as_ref().and_then(|mci| match &mci.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|value| value.parse::<usize>().ok())
+            .and_then(|i| videos.get(i)),
+        _ => None,
+    });
+
+    let Some(video) = video else {
+        msg.edit(
+            ctx,
+            Valeriyya::reply("")
+                .embed(
+                    Valeriyya::embed()
+                        .description("No song was picked.")
+                        .title("Search results"),
+                )
+                .components(vec![]),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if let Some(mci) = &picked {
+        mci.create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+            .await?;
+    }
+
+    let description = play_resolved(ctx, connect_to, vec![video.clone()].into()).await?;
+    msg.edit(
+        ctx,
+        Valeriyya::reply("")
+            .embed(
+                Valeriyya::embed()
+                    .description(description)
+                    .title("Song information"),
+            )
+            .components(vec![]),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use crate::{
+    structs::{QueueContext, TrackMetadata},
+    utils::{dj_check, restart_queue, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Seeks to a position in the currently playing song."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn seek(
+    ctx: Context<'_>,
+    #[description = "The position to seek to. (Example: 1m30s)"]
+    #[rest]
+    timestamp: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let handler_lock = ctx.data().songbird.get(guild_id);
+    let track = match &handler_lock {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
+    };
+
+    let (Some(handler_lock), Some(track)) = (handler_lock, track) else {
+        ctx.send(Valeriyya::reply("There is nothing playing right now.").ephemeral(true))
+            .await?;
+        return Ok(());
+    };
+
+    let Some(position) = parse_position(&timestamp) else {
+        ctx.send(
+            Valeriyya::reply("That's not a position in the song. (Example: 1m30s)").ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let metadata = track.data::<TrackMetadata>();
+    let duration = metadata.video.duration;
+
+    if !duration.is_zero() && position >= duration {
+        ctx.send(
+            Valeriyya::reply(format!(
+                "You can't seek past the end of the song. ({})",
+                Valeriyya::format_duration(duration)
+            ))
+            .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    ctx.defer().await?;
+    // ffmpeg can't seek a filtered track, so the song is started again from the position.
+    let filter = ctx.data().music_sessions.get(guild_id.get()).await.filter;
+    if !filter.is_off() || metadata.speed != 1.0 || !metadata.start.is_zero() {
+        let queue = QueueContext::new(ctx);
+        restart_queue(&mut *handler_lock.lock().await, &queue, position).await;
+    } else if let Err(err) = track.seek_async(position).await {
+        ctx.send(Valeriyya::reply(format!("Couldn't seek in the song: {}", err)).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    ctx.send(Valeriyya::reply(format!(
+        "Seeked to {}.",
+        Valeriyya::format_duration(position)
+    )))
+    .await?;
+
+    Ok(())
+}
+
+/// Parses the position, telling a literal zero apart from input that isn't a time at all.
+fn parse_position(timestamp: &str) -> Option<Duration> {
+    let seconds = Valeriyya::ms(timestamp);
+    if seconds > 0 {
+        return Some(Duration::from_secs(seconds as u64));
+    }
+
+    let zero = timestamp.trim().trim_end_matches(char::is_alphabetic);
+    (!zero.is_empty() && zero.chars().all(|c| c == '0')).then_some(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_position_reads_times() {
+        assert_eq!(parse_position("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_position("2h"), Some(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn parse_position_accepts_a_literal_zero() {
+        assert_eq!(parse_position("0"), Some(Duration::ZERO));
+        assert_eq!(parse_position(" 0s "), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_position_rejects_other_input() {
+        assert_eq!(parse_position("soon"), None);
+        assert_eq!(parse_position("90"), None);
+        assert_eq!(parse_position(""), None);
+    }
+}
@@ -0,0 +1,41 @@
+use rand::seq::SliceRandom;
+
+use crate::{
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Shuffles the songs in the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn shuffle(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let shuffled = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().modify_queue(|queue| {
+            if queue.len() < 3 {
+                return false;
+            }
+            queue.make_contiguous()[1..].shuffle(&mut rand::thread_rng());
+            true
+        }),
+        None => false,
+    };
+
+    if shuffled {
+        ctx.send(Valeriyya::reply("The queue has been shuffled."))
+            .await?;
+    } else {
+        ctx.send(
+            Valeriyya::reply("There are not enough songs in the queue to shuffle.").ephemeral(true),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,44 +1,151 @@
-use crate::{utils::Valeriyya, Context, Error};
+use std::collections::HashSet;
 
-#[doc = "Skips the currently playing song."]
+use serenity::all::User;
+
+use crate::{
+    structs::{SkipVote, TrackMetadata},
+    utils::{is_dj, is_member_dj, Valeriyya},
+    Context, Error,
+};
+
+/// What came of someone asking to skip the current song.
+pub enum SkipRequest {
+    /// The song was skipped by a DJ, its requester or a passing vote.
+    Skipped(String),
+    /// The vote was counted, more are needed.
+    Voted(String),
+    /// The user can't skip or vote right now.
+    Denied(String),
+}
+
+#[doc = "Skips the currently playing song, or votes to skip it."]
 #[poise::command(prefix_command, slash_command, category = "Music", aliases("s"))]
 pub async fn skip(ctx: Context<'_>) -> Result<(), Error> {
+    let reply = match request_skip(ctx, ctx.author()).await? {
+        SkipRequest::Skipped(message) | SkipRequest::Voted(message) => Valeriyya::reply(message),
+        SkipRequest::Denied(message) => Valeriyya::reply(message).ephemeral(true),
+    };
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Skips the current song for its requester or a DJ, otherwise counts the user's vote.
+/// Shared with the skip button of `nowplaying`, where the user isn't the command's author.
+pub async fn request_skip(ctx: Context<'_>, user: &User) -> Result<SkipRequest, Error> {
     let guild_id = ctx.guild_id().unwrap();
+    let bot_id = ctx.serenity_context().cache.current_user().id;
 
-    let channel_id = ctx
-        .guild()
-        .unwrap()
-        .voice_states
-        .get(&ctx.author().id)
-        .and_then(|voice_state| voice_state.channel_id);
+    let (channel_id, bot_channel_id) = {
+        let guild = ctx.guild().unwrap();
+        let channel_of = |user_id| {
+            guild
+                .voice_states
+                .get(&user_id)
+                .and_then(|voice_state| voice_state.channel_id)
+        };
+        (channel_of(user.id), channel_of(bot_id))
+    };
 
-    let _connect_to = match channel_id {
+    let connect_to = match channel_id {
         Some(channel) => channel,
         None => {
-            ctx.send(Valeriyya::reply("You are not in a voice channel.").ephemeral(true))
-                .await?;
-            return Ok(());
+            return Ok(SkipRequest::Denied(
+                "You are not in a voice channel.".to_string(),
+            ))
         }
     };
 
-    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        match queue.is_empty() {
-            false => {
-                let _ = queue.skip();
-                ctx.send(
-                    Valeriyya::reply("The song has been skipped successfully.").ephemeral(true),
-                )
-                .await?;
-            }
-            true => {
-                drop(handler);
-                ctx.send(Valeriyya::reply("There is no songs in the queue!").ephemeral(true))
-                    .await?;
-            }
-        };
+    let current = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
     };
 
-    Ok(())
+    let current = match current {
+        Some(current) => current,
+        None => {
+            return Ok(SkipRequest::Denied(
+                "There is no songs in the queue!".to_string(),
+            ))
+        }
+    };
+
+    let requester = current.data::<TrackMetadata>().requester;
+    let dj = if user.id == ctx.author().id {
+        is_dj(ctx).await?
+    } else {
+        is_member_dj(ctx, user.id).await?
+    };
+    if requester == user.id || dj {
+        skip_current(ctx).await;
+        return Ok(SkipRequest::Skipped(
+            "The song has been skipped successfully.".to_string(),
+        ));
+    }
+
+    if bot_channel_id != Some(connect_to) {
+        return Ok(SkipRequest::Denied(
+            "You need to be in my voice channel to vote.".to_string(),
+        ));
+    }
+
+    let listeners = {
+        let guild = ctx.guild().unwrap();
+        guild
+            .voice_states
+            .iter()
+            .filter(|voice_state| voice_state.channel_id == Some(connect_to))
+            .filter(|voice_state| {
+                voice_state
+                    .member
+                    .as_ref()
+                    .map_or(voice_state.user_id != bot_id, |member| !member.user.bot())
+            })
+            .count()
+    };
+
+    let db = Valeriyya::get_database(&ctx.data(), guild_id.get()).await?;
+    let needed = db.music.votes_needed(listeners);
+
+    let mut votes = 0;
+    ctx.data()
+        .music_sessions
+        .update(guild_id.get(), |session| {
+            let vote = session.skip_vote.get_or_insert_with(|| SkipVote {
+                track: current.uuid(),
+                voters: HashSet::new(),
+            });
+            if vote.track != current.uuid() {
+                vote.track = current.uuid();
+                vote.voters.clear();
+            }
+            vote.voters.insert(user.id);
+            votes = vote.voters.len();
+        })
+        .await;
+
+    if votes >= needed {
+        skip_current(ctx).await;
+        return Ok(SkipRequest::Skipped(format!(
+            "Vote passed ({}/{}), skipping the song.",
+            votes, needed
+        )));
+    }
+
+    Ok(SkipRequest::Voted(format!(
+        "{} voted to skip the song. ({}/{})",
+        user.name, votes, needed
+    )))
+}
+
+async fn skip_current(ctx: Context<'_>) {
+    let guild_id = ctx.guild_id().unwrap();
+
+    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
+        let _ = handler_lock.lock().await.queue().skip();
+    }
+    ctx.data()
+        .music_sessions
+        .update(guild_id.get(), |session| session.skip_vote = None)
+        .await;
 }
@@ -0,0 +1,70 @@
+use crate::{
+    structs::TrackMetadata,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Skips to a song in the queue."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn skipto(
+    ctx: Context<'_>,
+    #[description = "The position of the song to skip to."]
+    #[min = 1]
+    position: usize,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let handler_lock = match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            ctx.send(Valeriyya::reply("There is no songs in the queue!").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+
+    let skipped = queue.modify_queue(|queue| {
+        if position == 0 || position >= queue.len() {
+            return None;
+        }
+        Some(queue.drain(1..position).collect::<Vec<_>>())
+    });
+
+    let skipped = match skipped {
+        Some(skipped) => skipped,
+        None => {
+            drop(handler);
+            ctx.send(
+                Valeriyya::reply(format!("There is no song at position {}.", position))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    for queued in &skipped {
+        let _ = queued.stop();
+    }
+    let metadata = queue.current_queue()[1].data::<TrackMetadata>();
+    let _ = queue.skip();
+    drop(handler);
+
+    ctx.send(Valeriyya::reply(format!(
+        "Skipped to [{}]({}).",
+        metadata.video.title,
+        metadata.video.url()
+    )))
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use crate::{
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Stops the music and clears the queue without leaving the channel."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    match ctx.data().songbird.get(guild_id) {
+        Some(handler_lock) => {
+            handler_lock.lock().await.queue().stop();
+            ctx.data()
+                .music_sessions
+                .update(guild_id.get(), |session| session.clears += 1)
+                .await;
+            ctx.send(Valeriyya::reply(
+                "The music has been stopped and the queue cleared.",
+            ))
+            .await?;
+        }
+        None => {
+            ctx.send(Valeriyya::reply("I am not in a voice channel.").ephemeral(true))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
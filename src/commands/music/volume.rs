@@ -0,0 +1,62 @@
+use crate::{
+    structs::DEFAULT_VOLUME,
+    utils::{dj_check, Valeriyya},
+    Context, Error,
+};
+
+#[doc = "Changes the volume of the music in this guild."]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    category = "Music",
+    aliases("vol"),
+    default_member_permissions = "VIEW_CHANNEL",
+    check = "dj_check"
+)]
+pub async fn volume(
+    ctx: Context<'_>,
+    #[description = "The volume between 0 and 200."]
+    #[min = 0]
+    #[max = 200]
+    volume: Option<u16>,
+) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+
+    let mut db = Valeriyya::get_database(data, guild_id.get()).await?;
+
+    let volume = match volume {
+        Some(volume) if volume <= 200 => volume,
+        Some(_) => {
+            ctx.send(Valeriyya::reply("The volume has to be between 0 and 200.").ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+        None => {
+            ctx.send(Valeriyya::reply(format!(
+                "The volume is set to {}%.",
+                db.music.volume.unwrap_or(DEFAULT_VOLUME)
+            )))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    db = db.set_music(db.music.clone().set_volume(Some(volume)));
+    let track_volume = db.music.track_volume();
+    db.execute(data).await;
+
+    if let Some(handler_lock) = ctx.data().songbird.get(guild_id) {
+        for track in handler_lock.lock().await.queue().current_queue() {
+            let _ = track.set_volume(track_volume);
+        }
+    }
+
+    ctx.send(Valeriyya::reply(format!(
+        "The volume has been set to {}%.",
+        volume
+    )))
+    .await?;
+
+    Ok(())
+}
@@ -1,10 +1,16 @@
 use crate::{
-    structs::{GuildDbChannels, GuildDbRoles},
+    structs::{
+        CommandCategory, GuildDb, GuildDbChannels, GuildDbMusic, GuildDbRoles, RoleGrant,
+        DEFAULT_IDLE_TIMEOUT, DEFAULT_PLAYLIST_LIMIT, DEFAULT_VOLUME, DEFAULT_VOTE_SKIP,
+    },
     utils::Valeriyya,
     Context, Error,
 };
 
-use poise::serenity_prelude::Mentionable;
+use poise::{
+    serenity_prelude::{Mentionable, Permissions},
+    ChoiceParameter,
+};
 
 #[derive(poise::ChoiceParameter, Debug)]
 pub enum ChannelTypeChoices {
@@ -14,14 +20,28 @@ pub enum ChannelTypeChoices {
     Welcome,
     #[name = "starboard"]
     Starboard,
+    #[name = "appeals"]
+    Appeals,
 }
 
 #[doc = "Changes the settings in this guild."]
 #[poise::command(
     slash_command,
     category = "Settings",
-    subcommands("channel", "role"),
-    default_member_permissions = "MANAGE_GUILD",
+    subcommands(
+        "show",
+        "channel",
+        "role",
+        "permission",
+        "prefix",
+        "voteskip",
+        "playlistlimit",
+        "idletimeout",
+        "reset",
+        "unset"
+    ),
+    default_member_permissions = "VIEW_CHANNEL",
+    custom_data = "Permissions::MANAGE_GUILD",
     prefix_command,
     track_edits
 )]
@@ -29,6 +49,80 @@ pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+#[doc = "Shows the current settings of this guild."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+
+    let channel = |id: &Option<String>| {
+        id.as_ref()
+            .map(|id| format!("<#{}>", id))
+            .unwrap_or_else(|| "Not set".to_string())
+    };
+    let role = |id: &Option<String>| {
+        id.as_ref()
+            .map(|id| format!("<@&{}>", id))
+            .unwrap_or_else(|| "Not set".to_string())
+    };
+
+    let grants = if db.roles.grants.is_empty() {
+        "None".to_string()
+    } else {
+        db.roles
+            .grants
+            .iter()
+            .map(|grant| format!("<@&{}>: {}", grant.role, grant.category.name()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    ctx.send(
+        Valeriyya::reply_default().embed(
+            Valeriyya::embed()
+                .title("Guild settings")
+                .field(
+                    "Channels",
+                    format!(
+                        "Logs: {}\nWelcome: {}\nStarboard: {}\nAppeals: {}",
+                        channel(&db.channels.logs),
+                        channel(&db.channels.welcome),
+                        channel(&db.channels.starboard),
+                        channel(&db.channels.appeals)
+                    ),
+                    false,
+                )
+                .field(
+                    "Roles",
+                    format!(
+                        "Staff: {}\nDJ: {}",
+                        role(&db.roles.staff),
+                        role(&db.roles.dj)
+                    ),
+                    false,
+                )
+                .field("Permission grants", grants, false)
+                .field("Prefixes", format_prefixes(&db.get_prefixes()), false)
+                .field(
+                    "Music",
+                    format!(
+                        "Volume: {}%\nVote skip: {}% of listeners\nPlaylist limit: {} songs\nIdle timeout: {} minutes",
+                        db.music.volume.unwrap_or(DEFAULT_VOLUME),
+                        db.music.vote_skip.unwrap_or(DEFAULT_VOTE_SKIP),
+                        db.music.playlist_limit.unwrap_or(DEFAULT_PLAYLIST_LIMIT),
+                        db.music.idle_timeout.unwrap_or(DEFAULT_IDLE_TIMEOUT)
+                    ),
+                    false,
+                ),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
 #[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
 pub async fn channel(
     ctx: Context<'_>,
@@ -39,14 +133,16 @@ pub async fn channel(
     #[channel_types("Text")]
     channel: poise::serenity_prelude::GuildChannel,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild_id = ctx.guild_id().unwrap().get();
 
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
 
     if let ChannelTypeChoices::Logs = type_option {
         db = db.set_channels(
-            GuildDbChannels::default().set_logs_channel(Some(channel.id.to_string())),
+            db.channels
+                .clone()
+                .set_logs_channel(Some(channel.id.to_string())),
         );
         ctx.say(format!(
             "The logs channel has been updated to {}.",
@@ -55,7 +151,9 @@ pub async fn channel(
         .await?;
     } else if let ChannelTypeChoices::Welcome = type_option {
         db = db.set_channels(
-            GuildDbChannels::default().set_welcome_channel(Some(channel.id.to_string())),
+            db.channels
+                .clone()
+                .set_welcome_channel(Some(channel.id.to_string())),
         );
         ctx.say(format!(
             "The welcome channel has been updated to {}.",
@@ -64,16 +162,29 @@ pub async fn channel(
         .await?;
     } else if let ChannelTypeChoices::Starboard = type_option {
         db = db.set_channels(
-            GuildDbChannels::default().set_starboard_channel(Some(channel.id.to_string())),
+            db.channels
+                .clone()
+                .set_starboard_channel(Some(channel.id.to_string())),
         );
         ctx.say(format!(
             "The starboard channel has been updated to {}.",
             channel.mention()
         ))
         .await?;
+    } else if let ChannelTypeChoices::Appeals = type_option {
+        db = db.set_channels(
+            db.channels
+                .clone()
+                .set_appeals_channel(Some(channel.id.to_string())),
+        );
+        ctx.say(format!(
+            "The appeals channel has been updated to {}.",
+            channel.mention()
+        ))
+        .await?;
     }
 
-    db.execute(database).await;
+    db.execute(data).await;
 
     Ok(())
 }
@@ -84,6 +195,8 @@ pub enum RoleTypeChoices {
     Staff,
     #[name = "mute"]
     Mute,
+    #[name = "dj"]
+    Dj,
 }
 
 #[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
@@ -95,20 +208,379 @@ pub async fn role(
     #[description = "The role that will be used for the previous type."]
     role: poise::serenity_prelude::Role,
 ) -> Result<(), Error> {
-    let database = &ctx.data().database();
+    let data = &ctx.data();
     let guild_id = ctx.guild_id().unwrap().get();
 
-    let mut db = Valeriyya::get_database(database, guild_id).await?;
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
     if let RoleTypeChoices::Staff = type_option {
-        db = db.set_roles(GuildDbRoles::default().set_staff_role(Some(role.id.to_string())));
+        db = db.set_roles(db.roles.clone().set_staff_role(Some(role.id.to_string())));
         ctx.say(format!(
             "The staff role has been updated to {}.",
             role.mention()
         ))
         .await?;
+    } else if let RoleTypeChoices::Dj = type_option {
+        db = db.set_roles(db.roles.clone().set_dj_role(Some(role.id.to_string())));
+        ctx.say(format!(
+            "The DJ role has been updated to {}.",
+            role.mention()
+        ))
+        .await?;
+    };
+
+    db.execute(data).await;
+
+    Ok(())
+}
+
+#[doc = "Grants command categories to roles."]
+#[poise::command(
+    slash_command,
+    category = "Settings",
+    subcommands("grant", "revoke", "list"),
+    prefix_command,
+    track_edits
+)]
+pub async fn permission(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn grant(
+    ctx: Context<'_>,
+    #[description = "The command category to grant."] category: CommandCategory,
+    #[description = "The role that will be able to use the category."]
+    role: poise::serenity_prelude::Role,
+) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
+    db = db.set_roles(db.roles.clone().add_grant(RoleGrant {
+        role: role.id.to_string(),
+        category,
+    }));
+
+    ctx.say(format!(
+        "{} can now use {} commands.",
+        role.mention(),
+        category.name()
+    ))
+    .await?;
+
+    db.execute(data).await;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn revoke(
+    ctx: Context<'_>,
+    #[description = "The command category to revoke."] category: CommandCategory,
+    #[description = "The role that will lose access to the category."]
+    role: poise::serenity_prelude::Role,
+) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
+    db = db.set_roles(db.roles.clone().remove_grant(&RoleGrant {
+        role: role.id.to_string(),
+        category,
+    }));
+
+    ctx.say(format!(
+        "{} can no longer use {} commands.",
+        role.mention(),
+        category.name()
+    ))
+    .await?;
+
+    db.execute(data).await;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+
+    let mut embed = Valeriyya::embed().title("Permission rules");
+    for category in [
+        CommandCategory::Moderation,
+        CommandCategory::Settings,
+        CommandCategory::Dj,
+    ] {
+        let roles = db.roles.granted_roles(category);
+        let value = if roles.is_empty() {
+            "Discord permissions only".to_string()
+        } else {
+            roles
+                .iter()
+                .map(|role| format!("<@&{}>", role))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        embed = embed.field(category.name(), value, false);
+    }
+
+    ctx.send(Valeriyya::reply_default().embed(embed)).await?;
+
+    Ok(())
+}
+
+#[doc = "Changes the prefixes used for prefix commands in this guild."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn prefix(
+    ctx: Context<'_>,
+    #[description = "The new prefixes separated by spaces."]
+    #[rest]
+    prefixes: Option<String>,
+) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let mut db = Valeriyya::get_database(data, guild_id).await?;
+
+    let prefixes = match prefixes {
+        Some(prefixes) => prefixes
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<String>>(),
+        None => {
+            ctx.say(format!(
+                "The prefixes in this guild are: {}",
+                format_prefixes(&db.get_prefixes())
+            ))
+            .await?;
+            return Ok(());
+        }
     };
 
-    db.execute(database).await;
+    if prefixes.is_empty() || prefixes.len() > 5 || prefixes.iter().any(|p| p.len() > 5) {
+        ctx.send(
+            Valeriyya::reply("You can set up to 5 prefixes of at most 5 characters each.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    db = db.set_prefixes(prefixes);
+    db.clone().execute(data).await;
+
+    ctx.say(format!(
+        "The prefixes have been updated to {}.",
+        format_prefixes(&db.get_prefixes())
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Changes the percentage of listeners needed to vote skip a song."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn voteskip(
+    ctx: Context<'_>,
+    #[description = "The percentage of listeners between 1 and 100."]
+    #[min = 1]
+    #[max = 100]
+    percentage: u8,
+) -> Result<(), Error> {
+    if !(1..=100).contains(&percentage) {
+        ctx.send(Valeriyya::reply("The percentage has to be between 1 and 100.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+    db.clone()
+        .set_music(db.music.set_vote_skip(Some(percentage)))
+        .execute(data)
+        .await;
+
+    ctx.say(format!(
+        "Skipping a song now needs {}% of the listeners to vote.",
+        percentage
+    ))
+    .await?;
+
+    Ok(())
+}
+
+#[doc = "Changes how many songs a playlist can add to the queue at once."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn playlistlimit(
+    ctx: Context<'_>,
+    #[description = "The number of songs between 1 and 1000."]
+    #[min = 1]
+    #[max = 1000]
+    limit: usize,
+) -> Result<(), Error> {
+    if !(1..=1000).contains(&limit) {
+        ctx.send(Valeriyya::reply("The limit has to be between 1 and 1000.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+    db.clone()
+        .set_music(db.music.set_playlist_limit(Some(limit)))
+        .execute(data)
+        .await;
+
+    ctx.say(format!("Playlists now add up to {} songs.", limit))
+        .await?;
+
+    Ok(())
+}
+
+#[doc = "Changes how long the bot stays in an idle or empty voice channel."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn idletimeout(
+    ctx: Context<'_>,
+    #[description = "The number of minutes between 1 and 60."]
+    #[min = 1]
+    #[max = 60]
+    minutes: u64,
+) -> Result<(), Error> {
+    if !(1..=60).contains(&minutes) {
+        ctx.send(
+            Valeriyya::reply("The timeout has to be between 1 and 60 minutes.").ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+    db.clone()
+        .set_music(db.music.set_idle_timeout(Some(minutes)))
+        .execute(data)
+        .await;
+
+    ctx.say(format!(
+        "I will now leave after {} minutes without music or listeners.",
+        minutes
+    ))
+    .await?;
+
+    Ok(())
+}
+
+fn format_prefixes(prefixes: &[String]) -> String {
+    prefixes
+        .iter()
+        .map(|prefix| format!("`{}`", prefix))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[derive(poise::ChoiceParameter, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKey {
+    #[name = "logs"]
+    Logs,
+    #[name = "welcome"]
+    Welcome,
+    #[name = "starboard"]
+    Starboard,
+    #[name = "appeals"]
+    Appeals,
+    #[name = "staff"]
+    Staff,
+    #[name = "prefix"]
+    Prefix,
+    #[name = "permissions"]
+    Permissions,
+    #[name = "dj"]
+    Dj,
+    #[name = "volume"]
+    Volume,
+    #[name = "voteskip"]
+    VoteSkip,
+    #[name = "playlistlimit"]
+    PlaylistLimit,
+    #[name = "idletimeout"]
+    IdleTimeout,
+    #[name = "all"]
+    All,
+}
+
+/// Clears a single setting, leaving every other setting of the guild untouched.
+fn clear_setting(db: GuildDb, key: SettingKey) -> GuildDb {
+    let channels = db.channels.clone();
+    let roles = db.roles.clone();
+
+    match key {
+        SettingKey::Logs => db.set_channels(channels.set_logs_channel(None)),
+        SettingKey::Welcome => db.set_channels(channels.set_welcome_channel(None)),
+        SettingKey::Starboard => db.set_channels(channels.set_starboard_channel(None)),
+        SettingKey::Appeals => db.set_channels(channels.set_appeals_channel(None)),
+        SettingKey::Staff => db.set_roles(roles.set_staff_role(None)),
+        SettingKey::Dj => db.set_roles(roles.set_dj_role(None)),
+        SettingKey::Prefix => db.set_prefixes(Vec::new()),
+        SettingKey::Permissions => db.set_roles(GuildDbRoles {
+            grants: Vec::new(),
+            ..roles
+        }),
+        SettingKey::Volume => db.set_music(db.music.clone().set_volume(None)),
+        SettingKey::VoteSkip => db.set_music(db.music.clone().set_vote_skip(None)),
+        SettingKey::PlaylistLimit => db.set_music(db.music.clone().set_playlist_limit(None)),
+        SettingKey::IdleTimeout => db.set_music(db.music.clone().set_idle_timeout(None)),
+        SettingKey::All => db
+            .set_channels(GuildDbChannels::default())
+            .set_roles(GuildDbRoles::default())
+            .set_prefixes(Vec::new())
+            .set_music(GuildDbMusic::default()),
+    }
+}
+
+#[doc = "Resets a setting, or every setting, back to its default."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "The setting to reset."] key: SettingKey,
+) -> Result<(), Error> {
+    reset_setting(ctx, key).await
+}
+
+#[doc = "Removes the value of a setting, the same as `settings reset`."]
+#[poise::command(slash_command, category = "Settings", prefix_command, track_edits)]
+pub async fn unset(
+    ctx: Context<'_>,
+    #[description = "The setting to remove."] key: SettingKey,
+) -> Result<(), Error> {
+    reset_setting(ctx, key).await
+}
+
+async fn reset_setting(ctx: Context<'_>, key: SettingKey) -> Result<(), Error> {
+    let data = &ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+
+    let db = Valeriyya::get_database(data, guild_id).await?;
+    clear_setting(db, key).execute(data).await;
+
+    if key == SettingKey::All {
+        ctx.say("Every setting has been reset to its default.")
+            .await?;
+    } else {
+        ctx.say(format!(
+            "The {} setting has been reset to its default.",
+            key.name()
+        ))
+        .await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,245 @@
+use std::{
+    fmt, io,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use poise::{async_trait, ChoiceParameter};
+use songbird::input::{
+    core::io::{MediaSource, ReadOnlySource},
+    AudioStream, AudioStreamError, ChildContainer, Compose, Input, RawAdapter,
+};
+use tokio::{io::AsyncWriteExt, process::ChildStdin};
+
+use crate::{
+    resolver::{guarded_client, is_public_link, stream_url},
+    structs::{TrackSource, Video},
+};
+
+const SAMPLE_RATE: u32 = 48_000;
+const CHANNELS: u32 = 2;
+
+/// The slowest and fastest speed or pitch a filter can be set to.
+pub const MIN_FACTOR: f64 = 0.5;
+pub const MAX_FACTOR: f64 = 2.0;
+
+#[derive(poise::ChoiceParameter, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterPreset {
+    #[default]
+    #[name = "off"]
+    Off,
+    #[name = "bassboost"]
+    BassBoost,
+    #[name = "nightcore"]
+    Nightcore,
+    #[name = "vaporwave"]
+    Vaporwave,
+    #[name = "8d"]
+    EightD,
+    #[name = "karaoke"]
+    Karaoke,
+}
+
+/// The audio filter of a guild, applied to every song it plays through ffmpeg.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioFilter {
+    pub preset: FilterPreset,
+    pub speed: f64,
+    pub pitch: f64,
+}
+
+impl Default for AudioFilter {
+    fn default() -> Self {
+        Self::from_preset(FilterPreset::Off)
+    }
+}
+
+impl AudioFilter {
+    pub fn from_preset(preset: FilterPreset) -> Self {
+        let (speed, pitch) = match preset {
+            FilterPreset::Nightcore => (1.25, 1.25),
+            FilterPreset::Vaporwave => (0.8, 0.8),
+            _ => (1.0, 1.0),
+        };
+        Self {
+            preset,
+            speed,
+            pitch,
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_speed(mut self, speed: f64) -> Self {
+        self.speed = speed.clamp(MIN_FACTOR, MAX_FACTOR);
+        self
+    }
+
+    #[inline(always)]
+    pub fn set_pitch(mut self, pitch: f64) -> Self {
+        self.pitch = pitch.clamp(MIN_FACTOR, MAX_FACTOR);
+        self
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.preset == FilterPreset::Off && self.speed == 1.0 && self.pitch == 1.0
+    }
+
+    /// The `-af` filter graph ffmpeg plays the song through.
+    fn ffmpeg_filter(&self) -> String {
+        let mut filters: Vec<String> = match self.preset {
+            FilterPreset::BassBoost => vec!["bass=g=10".to_string()],
+            FilterPreset::EightD => vec!["apulsator=hz=0.125".to_string()],
+            FilterPreset::Karaoke => vec!["stereotools=mlev=0.015625".to_string()],
+            _ => Vec::new(),
+        };
+
+        // Resampling a faster stream back to the output rate raises the pitch and the speed
+        // together, atempo then corrects the speed alone.
+        if self.pitch != 1.0 {
+            filters.push(format!("aresample={}", SAMPLE_RATE));
+            filters.push(format!("asetrate={}", SAMPLE_RATE as f64 * self.pitch));
+            filters.push(format!("aresample={}", SAMPLE_RATE));
+        }
+
+        // atempo only takes factors between 0.5 and 2.
+        let mut tempo = self.speed / self.pitch;
+        while tempo < MIN_FACTOR {
+            filters.push(format!("atempo={}", MIN_FACTOR));
+            tempo /= MIN_FACTOR;
+        }
+        while tempo > MAX_FACTOR {
+            filters.push(format!("atempo={}", MAX_FACTOR));
+            tempo /= MAX_FACTOR;
+        }
+        if (tempo - 1.0).abs() > f64::EPSILON {
+            filters.push(format!("atempo={}", tempo));
+        }
+
+        if filters.is_empty() {
+            "anull".to_string()
+        } else {
+            filters.join(",")
+        }
+    }
+}
+
+impl fmt::Display for AudioFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_off() {
+            return write!(f, "Off");
+        }
+        write!(
+            f,
+            "{} • Speed: {}x • Pitch: {}x",
+            self.preset.name(),
+            self.speed,
+            self.pitch
+        )
+    }
+}
+
+/// A song played through ffmpeg with a filter, starting `start` into the song.
+#[derive(Clone)]
+pub struct FilteredSource {
+    pub video: Video,
+    pub filter: AudioFilter,
+    pub start: Duration,
+}
+
+impl FilteredSource {
+    /// Runs ffmpeg on the song at `url`, or on what is written to its stdin without one.
+    fn spawn_ffmpeg(&self, url: Option<&str>) -> io::Result<Child> {
+        let mut command = Command::new("ffmpeg");
+        match url {
+            Some(url) => command
+                .args(["-reconnect", "1", "-reconnect_streamed", "1"])
+                .args(["-reconnect_delay_max", "5"])
+                .args(["-ss", &format!("{:.3}", self.start.as_secs_f64())])
+                .args(["-i", url])
+                .stdin(Stdio::null()),
+            None => command
+                .args(["-ss", &format!("{:.3}", self.start.as_secs_f64())])
+                .args(["-i", "pipe:0"])
+                .stdin(Stdio::piped()),
+        };
+        command
+            .arg("-vn")
+            .args(["-af", &self.filter.ffmpeg_filter()])
+            .args(["-f", "f32le", "-ar", &SAMPLE_RATE.to_string()])
+            .args(["-ac", &CHANNELS.to_string()])
+            .args(["-loglevel", "error", "pipe:1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+    }
+
+    /// Direct links come from users, so ffmpeg gets the song through the guarded client
+    /// instead of following the link and its redirects itself.
+    async fn spawn_ffmpeg_piped(&self, url: &str) -> io::Result<Child> {
+        if !is_public_link(url) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the link points to a private address",
+            ));
+        }
+        let mut response = guarded_client()
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)?;
+
+        let mut child = self.spawn_ffmpeg(None)?;
+        let mut stdin = ChildStdin::from_std(child.stdin.take().unwrap())?;
+        // Ends once the song was sent or ffmpeg stopped reading it.
+        tokio::spawn(async move {
+            while let Ok(Some(chunk)) = response.chunk().await {
+                if stdin.write_all(&chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(child)
+    }
+}
+
+#[async_trait]
+impl Compose for FilteredSource {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let child = match self.video.source {
+            TrackSource::YouTube => {
+                let url = stream_url(&self.video.id)
+                    .await
+                    .map_err(|err| AudioStreamError::Fail(Box::new(err)))?;
+                self.spawn_ffmpeg(Some(&url))
+            }
+            TrackSource::Http => self.spawn_ffmpeg_piped(&self.video.id).await,
+        }
+        .map_err(|err| AudioStreamError::Fail(Box::new(err)))?;
+
+        Ok(AudioStream {
+            input: Box::new(RawAdapter::new(
+                ReadOnlySource::new(ChildContainer::from(child)),
+                SAMPLE_RATE,
+                CHANNELS,
+            )),
+            hint: None,
+        })
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+}
+
+impl From<FilteredSource> for Input {
+    fn from(source: FilteredSource) -> Self {
+        Input::Lazy(Box::new(source))
+    }
+}
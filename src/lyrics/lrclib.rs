@@ -0,0 +1,72 @@
+use poise::async_trait;
+use serde::Deserialize;
+
+use super::{parse_lrc, Lyrics, LyricsError, LyricsProvider};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    track_name: String,
+    artist_name: String,
+    #[serde(default)]
+    instrumental: bool,
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+/// The free lrclib.net database, which has synced lyrics for most songs.
+pub struct LrclibProvider;
+
+#[async_trait]
+impl LyricsProvider for LrclibProvider {
+    fn name(&self) -> &'static str {
+        "LRCLIB"
+    }
+
+    async fn find(
+        &self,
+        query: &str,
+        client: &reqwest::Client,
+    ) -> Result<Option<Lyrics>, LyricsError> {
+        let records = client
+            .get("https://lrclib.net/api/search")
+            .query(&[("q", query)])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| LyricsError::Network)?
+            .json::<Vec<Record>>()
+            .await
+            .map_err(|_| LyricsError::Network)?;
+
+        // Prefer a record with timed lines, the plain lyrics can be derived from them.
+        let record = records
+            .iter()
+            .filter(|record| !record.instrumental)
+            .find(|record| record.synced_lyrics.is_some())
+            .or_else(|| records.iter().find(|record| record.plain_lyrics.is_some()));
+
+        Ok(record.map(|record| {
+            let synced = record
+                .synced_lyrics
+                .as_deref()
+                .map(parse_lrc)
+                .filter(|lines| !lines.is_empty());
+            let plain = record.plain_lyrics.clone().unwrap_or_else(|| {
+                synced
+                    .iter()
+                    .flatten()
+                    .map(|line| line.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+
+            Lyrics {
+                title: record.track_name.clone(),
+                artist: record.artist_name.clone(),
+                plain,
+                synced,
+            }
+        }))
+    }
+}
@@ -0,0 +1,230 @@
+mod lrclib;
+
+use std::{fmt, time::Duration};
+
+use poise::async_trait;
+
+use crate::regex;
+
+pub use lrclib::LrclibProvider;
+
+/// Why lyrics could not be found for a song.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricsError {
+    NotFound,
+    Network,
+}
+
+impl fmt::Display for LyricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LyricsError::NotFound => write!(f, "I couldn't find any lyrics for that song."),
+            LyricsError::Network => write!(
+                f,
+                "I couldn't reach the lyrics provider, please try again in a moment."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LyricsError {}
+
+/// A line of synced lyrics and when it is sung.
+#[derive(Debug, Clone)]
+pub struct LyricLine {
+    pub time: Duration,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lyrics {
+    pub title: String,
+    pub artist: String,
+    pub plain: String,
+    /// Timed lines, when the provider has them, sorted by time.
+    pub synced: Option<Vec<LyricLine>>,
+}
+
+impl Lyrics {
+    /// The index of the synced line being sung at `position`.
+    pub fn current_line(&self, position: Duration) -> Option<usize> {
+        self.synced
+            .as_ref()?
+            .iter()
+            .rposition(|line| line.time <= position)
+    }
+}
+
+/// Reads the timed lines of LRC lyrics, a line can have several `[mm:ss.xx]` tags.
+pub fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
+    let tag = regex!(r"\[(\d+):(\d+(?:\.\d+)?)\]");
+
+    let mut lines: Vec<LyricLine> = lrc
+        .lines()
+        .flat_map(|line| {
+            let text = tag.replace_all(line, "").trim().to_string();
+            tag.captures_iter(line)
+                .filter_map(|caps| {
+                    let minutes: u64 = caps[1].parse().ok()?;
+                    let seconds: f64 = caps[2].parse().ok()?;
+                    Some(LyricLine {
+                        time: Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds),
+                        text: text.clone(),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    lines.sort_by_key(|line| line.time);
+    lines
+}
+
+/// Strips the "(Official Video)" style noise from a video title, leaving a lyrics query.
+pub fn clean_title(title: &str) -> String {
+    let noise = regex!(
+        r"(?i)[(\[][^)\]]*\b(?:official|video|audio|lyrics?|visuali[sz]er|hd|hq|4k|remaster(?:ed)?|mv|live)\b[^)\]]*[)\]]"
+    );
+    let cleaned = noise.replace_all(title, "");
+    let cleaned = regex!(r"(?i)\s*\|.*$").replace(&cleaned, "");
+
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A source of lyrics, asked in order by the finder until one has the song.
+#[async_trait]
+pub trait LyricsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn find(
+        &self,
+        query: &str,
+        client: &reqwest::Client,
+    ) -> Result<Option<Lyrics>, LyricsError>;
+}
+
+/// Looks up lyrics through the first provider that has them.
+pub struct LyricsFinder {
+    providers: Vec<Box<dyn LyricsProvider>>,
+    client: reqwest::Client,
+}
+
+impl LyricsFinder {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn provider(mut self, provider: impl LyricsProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Only reports a network error when no other provider had the song either.
+    pub async fn find(&self, query: &str) -> Result<Lyrics, LyricsError> {
+        let mut error = LyricsError::NotFound;
+        for provider in &self.providers {
+            match provider.find(query.trim(), &self.client).await {
+                Ok(Some(lyrics)) => return Ok(lyrics),
+                Ok(None) => {}
+                Err(err) => {
+                    tracing::warn!("{} lyrics lookup failed: {}", provider.name(), err);
+                    error = err;
+                }
+            }
+        }
+        Err(error)
+    }
+}
+
+impl Default for LyricsFinder {
+    fn default() -> Self {
+        Self::new().provider(LrclibProvider)
+    }
+}
+
+impl fmt::Debug for LyricsFinder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|provider| provider.name()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lyrics(synced: Option<Vec<LyricLine>>) -> Lyrics {
+        Lyrics {
+            title: "Song".to_string(),
+            artist: "Artist".to_string(),
+            plain: String::new(),
+            synced,
+        }
+    }
+
+    #[test]
+    fn parse_lrc_reads_and_sorts_timed_lines() {
+        let lines = parse_lrc("[ar:Artist]\n[00:12.50]Second\n[00:01]First\n\n[01:02.25]\nno tag");
+
+        let times: Vec<_> = lines.iter().map(|line| line.time).collect();
+        assert_eq!(
+            times,
+            [
+                Duration::from_secs(1),
+                Duration::from_millis(12_500),
+                Duration::from_millis(62_250),
+            ]
+        );
+        let texts: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["First", "Second", ""]);
+    }
+
+    #[test]
+    fn parse_lrc_repeats_lines_with_several_tags() {
+        let lines = parse_lrc("[00:10.00][00:30.00] Chorus ");
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].time, Duration::from_secs(10));
+        assert_eq!(lines[1].time, Duration::from_secs(30));
+        assert!(lines.iter().all(|line| line.text == "Chorus"));
+    }
+
+    #[test]
+    fn current_line_follows_the_position() {
+        let lyrics = lyrics(Some(parse_lrc("[00:05]One\n[00:10]Two\n[00:15]Three")));
+
+        assert_eq!(lyrics.current_line(Duration::from_secs(2)), None);
+        assert_eq!(lyrics.current_line(Duration::from_secs(5)), Some(0));
+        assert_eq!(lyrics.current_line(Duration::from_secs(12)), Some(1));
+        assert_eq!(lyrics.current_line(Duration::from_secs(90)), Some(2));
+    }
+
+    #[test]
+    fn current_line_without_synced_lyrics() {
+        assert_eq!(lyrics(None).current_line(Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn clean_title_strips_video_noise() {
+        assert_eq!(
+            clean_title("Artist - Song (Official Music Video)"),
+            "Artist - Song"
+        );
+        assert_eq!(clean_title("Artist - Song [HD] (Lyrics)"), "Artist - Song");
+        assert_eq!(clean_title("Artist - Song | Some Label"), "Artist - Song");
+    }
+
+    #[test]
+    fn clean_title_keeps_the_rest() {
+        assert_eq!(
+            clean_title("Artist - Song (feat. Someone)"),
+            "Artist - Song (feat. Someone)"
+        );
+        assert_eq!(clean_title("  Artist   -  Song "), "Artist - Song");
+    }
+}
@@ -1,9 +1,14 @@
+mod appeals;
 mod commands;
+mod filters;
+mod lyrics;
+mod resolver;
 mod structs;
 mod utils;
 
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use ::token_manager::TokenManager;
 use dotenv::dotenv;
@@ -12,8 +17,10 @@ use poise::serenity_prelude::{self, GatewayIntents};
 use serenity::all::Token;
 use tokio::sync::Mutex;
 
-use crate::structs::ValeriyyaEventHandler;
-use crate::utils::{initialize_database, on_error};
+use crate::lyrics::LyricsFinder;
+use crate::resolver::{MetadataCache, Resolver};
+use crate::structs::{GuildCache, InactivityManager, MusicSessions, ValeriyyaEventHandler};
+use crate::utils::{command_check, initialize_database, on_error, queue_saver, strip_prefix};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -23,6 +30,11 @@ pub struct Data {
     db_client: Client,
     api_token_manager: Arc<Mutex<TokenManager>>,
     songbird: Arc<songbird::Songbird>,
+    guild_cache: GuildCache,
+    music_sessions: Arc<MusicSessions>,
+    inactivity: Arc<InactivityManager>,
+    resolver: Resolver,
+    lyrics: LyricsFinder,
 }
 
 impl Data {
@@ -59,6 +71,24 @@ async fn init() -> Result<(), Error> {
             commands::music::leave(),
             commands::music::join(),
             commands::music::loop_music(),
+            commands::music::queue(),
+            commands::music::remove(),
+            commands::music::move_track(),
+            commands::music::shuffle(),
+            commands::music::clear(),
+            commands::music::skipto(),
+            commands::music::nowplaying(),
+            commands::music::pause(),
+            commands::music::resume(),
+            commands::music::stop(),
+            commands::music::seek(),
+            commands::music::volume(),
+            commands::music::search(),
+            commands::music::playlist(),
+            commands::music::filter(),
+            commands::music::lyrics(),
+            commands::music::history(),
+            commands::music::previous(),
             commands::moderation::ban(),
             commands::moderation::kick(),
             commands::moderation::mute(),
@@ -68,9 +98,11 @@ async fn init() -> Result<(), Error> {
             commands::settings::settings(),
             commands::application::star(),
             commands::info::reminder(),
+            commands::info::cache(),
         ],
         prefix_options: poise::PrefixFrameworkOptions {
-            prefix: Some("!".into()),
+            prefix: None,
+            stripped_dynamic_prefix: Some(|ctx, msg, data| Box::pin(strip_prefix(ctx, msg, data))),
             mention_as_prefix: true,
             ignore_bots: true,
             edit_tracker: Some(std::sync::Arc::new(poise::EditTracker::for_timespan(
@@ -83,6 +115,7 @@ async fn init() -> Result<(), Error> {
             owners.insert(poise::serenity_prelude::UserId::new(206360333881704449));
             owners
         },
+        command_check: Some(|ctx| Box::pin(command_check(ctx))),
         on_error: |error| Box::pin(on_error(error)),
         initialize_owners: true,
         skip_checks_for_owners: true,
@@ -93,18 +126,27 @@ async fn init() -> Result<(), Error> {
         db_client,
         api_token_manager: token_manager,
         songbird: songbird.clone(),
+        guild_cache: GuildCache::new(1000, Duration::from_secs(300)),
+        music_sessions: Arc::new(MusicSessions::default()),
+        inactivity: Arc::new(InactivityManager::default()),
+        resolver: Resolver::default(),
+        lyrics: LyricsFinder::default(),
     };
 
+    MetadataCache::new(&data.database())
+        .create_ttl_index()
+        .await;
+
+    let data = Arc::new(data);
+    tokio::spawn(queue_saver(data.clone()));
     let framework = poise::Framework::new(options);
-    let event_handler = ValeriyyaEventHandler {
-        database: data.database().into(),
-    };
+    let event_handler = ValeriyyaEventHandler { data: data.clone() };
 
     let mut client = serenity_prelude::ClientBuilder::new(discord_token.clone(), discord_intents)
         .voice_manager::<songbird::Songbird>(songbird)
         .event_handler(event_handler)
         .framework(framework)
-        .data(std::sync::Arc::new(data))
+        .data(data)
         .await
         .unwrap();
 
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use bson::doc;
+use futures::{future, TryStreamExt};
+use mongodb::{options::IndexOptions, Collection, Database, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use crate::structs::{TrackSource, Video};
+
+/// How long a cached lookup is trusted before the track is looked up again.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// What a cached track was looked up by.
+pub enum CacheKey<'a> {
+    Query(&'a str),
+    Spotify(&'a str),
+    YouTube(&'a str),
+}
+
+impl fmt::Display for CacheKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Searches differing only in case or spacing find the same video.
+            Self::Query(query) => write!(
+                f,
+                "query:{}",
+                query
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .to_lowercase()
+            ),
+            Self::Spotify(id) => write!(f, "spotify:{}", id),
+            Self::YouTube(id) => write!(f, "youtube:{}", id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedTrack {
+    #[serde(rename = "_id")]
+    key: String,
+    id: String,
+    title: String,
+    duration_ms: u64,
+    cached_at: bson::DateTime,
+}
+
+impl From<CachedTrack> for Video {
+    fn from(track: CachedTrack) -> Self {
+        Self {
+            id: track.id,
+            title: track.title,
+            duration: Duration::from_millis(track.duration_ms),
+            source: TrackSource::YouTube,
+        }
+    }
+}
+
+/// Resolved YouTube tracks stored in MongoDB, so repeated lookups skip the APIs and yt-dlp.
+/// Failing to read or write the cache only costs a fresh lookup.
+#[derive(Clone)]
+pub struct MetadataCache {
+    collection: Collection<CachedTrack>,
+}
+
+impl MetadataCache {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection::<CachedTrack>("metadata_cache"),
+        }
+    }
+
+    /// Lets MongoDB drop expired entries, lookups ignore them until it does.
+    pub async fn create_ttl_index(&self) {
+        let index = IndexModel::builder()
+            .keys(doc! { "cached_at": 1 })
+            .options(IndexOptions::builder().expire_after(CACHE_TTL).build())
+            .build();
+
+        if let Err(err) = self.collection.create_index(index).await {
+            tracing::warn!("Failed to create the metadata cache index: {}", err);
+        }
+    }
+
+    fn fresh_since() -> bson::DateTime {
+        bson::DateTime::from_system_time(SystemTime::now() - CACHE_TTL)
+    }
+
+    pub async fn get(&self, key: CacheKey<'_>) -> Option<Video> {
+        match self
+            .collection
+            .find_one(doc! { "_id": key.to_string(), "cached_at": { "$gt": Self::fresh_since() } })
+            .await
+        {
+            Ok(track) => track.map(Video::from),
+            Err(err) => {
+                tracing::warn!("Failed to read the metadata cache: {}", err);
+                None
+            }
+        }
+    }
+
+    /// The cached videos among `ids`, keyed by their id.
+    pub async fn get_videos(&self, ids: &[String]) -> HashMap<String, Video> {
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| CacheKey::YouTube(id).to_string())
+            .collect();
+
+        let tracks = match self
+            .collection
+            .find(doc! { "_id": { "$in": keys }, "cached_at": { "$gt": Self::fresh_since() } })
+            .await
+        {
+            Ok(cursor) => cursor.try_collect::<Vec<_>>().await,
+            Err(err) => Err(err),
+        };
+
+        match tracks {
+            Ok(tracks) => tracks
+                .into_iter()
+                .map(|track| (track.id.clone(), Video::from(track)))
+                .collect(),
+            Err(err) => {
+                tracing::warn!("Failed to read the metadata cache: {}", err);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Videos found by earlier searches starting with `partial`, the most recent first.
+    pub async fn find_queries(&self, partial: &str, limit: usize) -> Vec<Video> {
+        let prefix = format!("^{}", regex::escape(&CacheKey::Query(partial).to_string()));
+        let tracks = match self
+            .collection
+            .find(doc! { "_id": { "$regex": prefix }, "cached_at": { "$gt": Self::fresh_since() } })
+            .sort(doc! { "cached_at": -1 })
+            .limit(limit as i64)
+            .await
+        {
+            Ok(cursor) => cursor.try_collect::<Vec<_>>().await,
+            Err(err) => Err(err),
+        };
+
+        match tracks {
+            Ok(tracks) => {
+                let mut videos: Vec<Video> = Vec::new();
+                for track in tracks {
+                    if !videos.iter().any(|video| video.id == track.id) {
+                        videos.push(Video::from(track));
+                    }
+                }
+                videos
+            }
+            Err(err) => {
+                tracing::warn!("Failed to read the metadata cache: {}", err);
+                Vec::new()
+            }
+        }
+    }
+
+    pub async fn insert(&self, key: CacheKey<'_>, video: &Video) {
+        if video.source != TrackSource::YouTube {
+            return;
+        }
+
+        let key = key.to_string();
+        let track = CachedTrack {
+            key: key.clone(),
+            id: video.id.clone(),
+            title: video.title.clone(),
+            duration_ms: video.duration.as_millis() as u64,
+            cached_at: bson::DateTime::now(),
+        };
+
+        if let Err(err) = self
+            .collection
+            .replace_one(doc! { "_id": key }, track)
+            .upsert(true)
+            .await
+        {
+            tracing::warn!("Failed to write the metadata cache: {}", err);
+        }
+    }
+
+    pub async fn insert_videos(&self, videos: &[Video]) {
+        future::join_all(
+            videos
+                .iter()
+                .map(|video| self.insert(CacheKey::YouTube(&video.id), video)),
+        )
+        .await;
+    }
+}
@@ -0,0 +1,379 @@
+use std::{io::Cursor, net::IpAddr, sync::Arc, time::Duration};
+
+use once_cell::sync::Lazy;
+use poise::async_trait;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE},
+    redirect::Policy,
+    Url,
+};
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
+
+use super::{Resolved, ResolverContext, TrackProvider};
+use crate::{
+    regex,
+    structs::{MetadataError, TrackSource, Video},
+};
+
+/// How much of a file past its ID3v2 tag is downloaded to read its tags and duration.
+const PROBE_BYTES: usize = 512 * 1024;
+/// The most downloaded to probe a file, ID3v2 tags can hold large cover art.
+const MAX_PROBE_BYTES: usize = 16 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+
+/// Links are given by users, so this client won't connect or follow redirects to private
+/// addresses. Host names are checked on the same lookup the connection uses, so a name can't
+/// point somewhere else by the time the song is played.
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_public_host(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }))
+        .build()
+        .unwrap()
+});
+
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "flac", "ogg", "oga", "opus", "m4a", "aac", "wav"];
+
+/// What the tags of an audio file say about it, any of it can be missing.
+#[derive(Default)]
+struct FileTags {
+    title: Option<String>,
+    artist: Option<String>,
+    duration: Option<Duration>,
+}
+
+impl FileTags {
+    fn read_revision(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => self.title = Some(tag.value.to_string()),
+                Some(StandardTagKey::Artist) => self.artist = Some(tag.value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Probes the start of a file, which is enough for the tags and usually the duration.
+    fn probe(bytes: Vec<u8>, extension: Option<&str>, mime_type: Option<&str>) -> Self {
+        let mut tags = Self::default();
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+        if let Some(mime_type) = mime_type {
+            hint.mime_type(mime_type);
+        }
+
+        let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        let Ok(mut probed) = symphonia::default::get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        ) else {
+            return tags;
+        };
+
+        // ID3 tags come before the container, the others are read by the format itself.
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            tags.read_revision(revision);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            tags.read_revision(revision);
+        }
+
+        tags.duration = probed.format.default_track().and_then(|track| {
+            let params = &track.codec_params;
+            let time = params.time_base?.calc_time(params.n_frames?);
+            Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+        });
+        tags
+    }
+}
+
+/// Whether the address is reachable from the internet, not the bot's own network.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // 0.0.0.0/8 and the 100.64.0.0/10 carrier-grade NAT range aren't covered by std.
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(ip.into()),
+            // Unique local fc00::/7 and link-local fe80::/10 addresses.
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.segments()[0] & 0xfe00 == 0xfc00
+                    || ip.segments()[0] & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The address of a url whose host is written as one.
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    url.host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Rejects local host names and private addresses, without looking the name up.
+fn is_public_host(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    if let Some(ip) = host_ip(url) {
+        return is_public_ip(ip);
+    }
+
+    let Some(host) = url.host_str().map(str::to_lowercase) else {
+        return false;
+    };
+    let host = host.trim_end_matches('.');
+    !(host == "localhost"
+        || host.ends_with(".localhost")
+        || host.ends_with(".local")
+        || host.ends_with(".internal"))
+}
+
+/// The client for fetching links given by users, for playback as well as resolving.
+pub fn guarded_client() -> reqwest::Client {
+    CLIENT.clone()
+}
+
+/// Whether a link given by a user, or loaded back from the database, may be played.
+pub fn is_public_link(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| is_public_host(&url))
+}
+
+/// Looks host names up for the guarded client, leaving out private addresses.
+struct PublicResolver;
+
+impl PublicResolver {
+    async fn lookup(host: String) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+        let addresses: Vec<_> = tokio::net::lookup_host((host.as_str(), 0))
+            .await?
+            .filter(|address| is_public_ip(address.ip()))
+            .collect();
+        if addresses.is_empty() {
+            return Err(format!("{} has no public address", host).into());
+        }
+        Ok(Box::new(addresses.into_iter()))
+    }
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(Self::lookup(name.as_str().to_string()))
+    }
+}
+
+/// Looks the host up, a public name can still point at a private address.
+async fn resolves_publicly(url: &Url) -> bool {
+    if let Some(ip) = host_ip(url) {
+        return is_public_ip(ip);
+    }
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return false;
+    };
+
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addresses) => {
+            let addresses: Vec<_> = addresses.collect();
+            !addresses.is_empty() && addresses.iter().all(|address| is_public_ip(address.ip()))
+        }
+        Err(_) => false,
+    }
+}
+
+/// The size of the ID3v2 tag a file starts with, stored in its header as a syncsafe integer.
+fn id3v2_size(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 10 || !bytes.starts_with(b"ID3") {
+        return None;
+    }
+    let size = bytes[6..10]
+        .iter()
+        .fold(0, |size, byte| (size << 7) | usize::from(byte & 0x7f));
+    let footer = if bytes[5] & 0x10 != 0 { 10 } else { 0 };
+    Some(10 + size + footer)
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// The decoded file name at the end of a url's path.
+fn file_name(url: &str) -> Option<String> {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|file| !file.is_empty())
+        .map(|file| urlencoding::decode(file).map_or(file.to_string(), |f| f.into_owned()))
+}
+
+/// Direct links to audio files, attachments and internet radio streams, played through
+/// songbird's `HttpRequest` input.
+pub struct HttpAudioProvider;
+
+#[async_trait]
+impl TrackProvider for HttpAudioProvider {
+    fn name(&self) -> &'static str {
+        "HTTP audio"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        regex!(r"^https?://\S+$").is_match(query) && is_public_link(query)
+    }
+
+    async fn resolve(
+        &self,
+        query: &str,
+        _ctx: &ResolverContext,
+    ) -> Result<Resolved, MetadataError> {
+        let url = Url::parse(query).map_err(|_| MetadataError::NotFound)?;
+        if !resolves_publicly(&url).await {
+            return Err(MetadataError::NotFound);
+        }
+
+        let mut response = CLIENT
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| MetadataError::Network)?;
+
+        let file_name = file_name(query);
+        let extension = file_name
+            .as_deref()
+            .and_then(|file| file.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_lowercase())
+            .filter(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()));
+
+        let headers = response.headers();
+        let content_type = header(headers, CONTENT_TYPE.as_str())
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|content_type| content_type.trim().to_lowercase());
+        let is_audio = match content_type.as_deref() {
+            Some(content_type) => {
+                content_type.starts_with("audio/")
+                    || matches!(content_type, "application/ogg" | "video/mp4" | "video/ogg")
+                    || (content_type == "application/octet-stream" && extension.is_some())
+            }
+            None => extension.is_some(),
+        };
+        if !is_audio {
+            return Err(MetadataError::NotFound);
+        }
+
+        // Radio streams announce themselves with icy headers and have no length.
+        let station = header(headers, "icy-name").map(str::to_string);
+        let is_stream = station.is_some()
+            || header(headers, "icy-br").is_some()
+            || !headers.contains_key(CONTENT_LENGTH);
+
+        let tags = if is_stream {
+            FileTags::default()
+        } else {
+            let mut bytes = Vec::new();
+            let mut limit = PROBE_BYTES;
+            while bytes.len() < limit {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+                    _ => break,
+                }
+                // The tags and the audio after them have to fit, not just the start of the tag.
+                if let Some(tag_size) = id3v2_size(&bytes) {
+                    limit = (tag_size + PROBE_BYTES).min(MAX_PROBE_BYTES);
+                }
+            }
+            FileTags::probe(bytes, extension.as_deref(), content_type.as_deref())
+        };
+
+        let title = match (tags.artist, tags.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title,
+            _ => station.or(file_name).unwrap_or_else(|| query.to_string()),
+        };
+
+        Ok(vec![Video {
+            id: query.to_string(),
+            title,
+            duration: tags.duration.unwrap_or_default(),
+            source: TrackSource::Http,
+        }]
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(url: &str) -> bool {
+        is_public_link(url)
+    }
+
+    #[test]
+    fn public_hosts_are_accepted() {
+        assert!(public(
+            "https://cdn.discordapp.com/attachments/1/2/song.mp3"
+        ));
+        assert!(public("http://8.8.8.8/stream"));
+        assert!(public("http://[2606:4700::1111]/stream"));
+    }
+
+    #[test]
+    fn private_hosts_are_rejected() {
+        assert!(!public("http://localhost:8080/song.mp3"));
+        assert!(!public("http://127.0.0.1/song.mp3"));
+        assert!(!public("http://2130706433/song.mp3"));
+        assert!(!public("http://10.0.0.5/song.mp3"));
+        assert!(!public("http://192.168.1.1/song.mp3"));
+        assert!(!public("http://169.254.169.254/latest/meta-data"));
+        assert!(!public("http://100.64.0.1/song.mp3"));
+        assert!(!public("http://[::1]/song.mp3"));
+        assert!(!public("http://[fd00::1]/song.mp3"));
+        assert!(!public("http://[::ffff:127.0.0.1]/song.mp3"));
+        assert!(!public("http://metadata.google.internal/song.mp3"));
+    }
+
+    #[test]
+    fn id3v2_size_reads_the_syncsafe_header() {
+        // 0x01 0x00 0x00 0x00 as a syncsafe integer is 1 << 21.
+        let header = [b'I', b'D', b'3', 4, 0, 0, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(id3v2_size(&header), Some(10 + (1 << 21)));
+
+        let with_footer = [b'I', b'D', b'3', 4, 0, 0x10, 0, 0, 0x01, 0x7f];
+        assert_eq!(id3v2_size(&with_footer), Some(10 + 255 + 10));
+    }
+
+    #[test]
+    fn id3v2_size_without_a_tag() {
+        assert_eq!(id3v2_size(b"fLaC\0\0\0\x22\x10\x00"), None);
+        assert_eq!(id3v2_size(b"ID3"), None);
+    }
+}
@@ -0,0 +1,231 @@
+mod cache;
+mod http;
+mod spotify;
+mod youtube;
+mod ytdlp;
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use ::token_manager::TokenManager;
+use poise::async_trait;
+use tokio::{
+    sync::{mpsc, Mutex, Semaphore},
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{
+    structs::{MetadataError, Video},
+    Data,
+};
+
+pub use cache::MetadataCache;
+pub use http::{guarded_client, is_public_link, HttpAudioProvider};
+pub use spotify::{
+    SpotifyAlbumProvider, SpotifyArtistProvider, SpotifyPlaylistProvider, SpotifyTrackProvider,
+};
+pub use youtube::{YouTubePlaylistProvider, YouTubeSearchProvider, YouTubeVideoProvider};
+pub use ytdlp::stream_url;
+
+/// What a provider can use to resolve a query, built once per lookup.
+#[derive(Clone)]
+pub struct ResolverContext {
+    pub reqwest: reqwest::Client,
+    pub youtube_api_key: String,
+    /// The most tracks a playlist, album or artist adds to the queue.
+    pub playlist_limit: usize,
+    quota: Arc<ApiQuota>,
+    cache: MetadataCache,
+    token_manager: Arc<Mutex<TokenManager>>,
+}
+
+impl ResolverContext {
+    pub async fn new(data: &Data, playlist_limit: usize) -> Self {
+        let youtube_api_key = data.api_token_manager.lock().await.get_youtube_token();
+
+        Self {
+            reqwest: reqwest::Client::new(),
+            youtube_api_key,
+            playlist_limit,
+            quota: data.resolver.quota.clone(),
+            cache: MetadataCache::new(&data.database()),
+            token_manager: data.api_token_manager.clone(),
+        }
+    }
+
+    /// Whether YouTube lookups can go through the Data API instead of yt-dlp.
+    pub fn use_api(&self) -> bool {
+        !self.youtube_api_key.is_empty() && !self.quota.is_exhausted()
+    }
+
+    pub async fn spotify_token(&self) -> Result<String, MetadataError> {
+        self.token_manager
+            .lock()
+            .await
+            .get_spotify_token()
+            .await
+            .map_err(|_| MetadataError::Network)
+    }
+}
+
+/// How long lookups skip the Data API after it reported that the quota ran out.
+const QUOTA_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Remembers when the YouTube Data API quota ran out, so lookups go through yt-dlp meanwhile.
+#[derive(Debug, Default)]
+pub struct ApiQuota {
+    exhausted_at: StdMutex<Option<Instant>>,
+}
+
+impl ApiQuota {
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted_at
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() < QUOTA_BACKOFF)
+    }
+
+    pub fn exhaust(&self) {
+        tracing::warn!("YouTube Data API quota exceeded, falling back to yt-dlp");
+        *self.exhausted_at.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// Tracks that are still being looked up after the first ones were returned.
+pub struct PendingTracks {
+    pub count: usize,
+    pub receiver: mpsc::Receiver<Vec<Video>>,
+    /// How many of the tracks could not be found, known once everything was sent.
+    pub skipped: JoinHandle<usize>,
+}
+
+/// The tracks a query resolved to. Large collections return their first track right away
+/// and send the rest through `pending` in batches as they resolve. `skipped` counts the
+/// private, deleted or otherwise unplayable entries that were left out.
+pub struct Resolved {
+    pub tracks: Vec<Video>,
+    pub pending: Option<PendingTracks>,
+    pub skipped: usize,
+}
+
+impl From<Vec<Video>> for Resolved {
+    fn from(tracks: Vec<Video>) -> Self {
+        Self {
+            tracks,
+            pending: None,
+            skipped: 0,
+        }
+    }
+}
+
+/// A source of tracks, picked by the resolver when `matches` accepts the query.
+#[async_trait]
+pub trait TrackProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn matches(&self, query: &str) -> bool;
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError>;
+}
+
+/// How many yt-dlp searches for suggestions run at once, keystrokes past that only get what
+/// is cached.
+const MAX_CONCURRENT_SUGGESTIONS: usize = 2;
+
+/// How long a suggestion search may take, Discord drops autocomplete responses after 3 seconds.
+const SUGGESTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Resolves `play` queries through the first provider that accepts them.
+pub struct Resolver {
+    providers: Vec<Box<dyn TrackProvider>>,
+    quota: Arc<ApiQuota>,
+    suggestions: Semaphore,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            quota: Arc::new(ApiQuota::default()),
+            suggestions: Semaphore::new(MAX_CONCURRENT_SUGGESTIONS),
+        }
+    }
+
+    #[inline(always)]
+    pub fn provider(mut self, provider: impl TrackProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    pub async fn resolve(
+        &self,
+        query: &str,
+        ctx: &ResolverContext,
+    ) -> Result<Resolved, MetadataError> {
+        let query = query.trim();
+        match self
+            .providers
+            .iter()
+            .find(|provider| provider.matches(query))
+        {
+            Some(provider) => provider.resolve(query, ctx).await,
+            None => Err(MetadataError::NotFound),
+        }
+    }
+
+    /// The best YouTube matches for a search, for the author to pick from.
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        ctx: &ResolverContext,
+    ) -> Result<Vec<Video>, MetadataError> {
+        youtube::search_videos(query.trim(), limit, ctx).await
+    }
+
+    /// Suggestions while a search is typed. They come from earlier searches in the cache or
+    /// from yt-dlp, never from the Data API, which would spend quota on every keystroke. The
+    /// partial query isn't cached, playing it later should search it for real.
+    pub async fn suggest(&self, query: &str, limit: usize, ctx: &ResolverContext) -> Vec<Video> {
+        let query = query.trim();
+        let cached = ctx.cache.find_queries(query, limit).await;
+        if !cached.is_empty() {
+            return cached;
+        }
+
+        let Ok(_permit) = self.suggestions.try_acquire() else {
+            return Vec::new();
+        };
+        match timeout(SUGGESTION_TIMEOUT, ytdlp::search(query, limit)).await {
+            Ok(Ok(videos)) => videos,
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Default for Resolver {
+    /// Every builtin provider, with YouTube search last as it accepts any query.
+    fn default() -> Self {
+        Self::new()
+            .provider(SpotifyTrackProvider)
+            .provider(SpotifyPlaylistProvider)
+            .provider(SpotifyAlbumProvider)
+            .provider(SpotifyArtistProvider)
+            .provider(YouTubePlaylistProvider)
+            .provider(YouTubeVideoProvider)
+            .provider(HttpAudioProvider)
+            .provider(YouTubeSearchProvider)
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|provider| provider.name()))
+            .finish()
+    }
+}
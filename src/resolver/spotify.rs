@@ -0,0 +1,288 @@
+use futures::{stream, StreamExt};
+use poise::async_trait;
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::{
+    cache::CacheKey,
+    youtube::{cache_queries, get_videos, resolve_query, search_video_id},
+    PendingTracks, Resolved, ResolverContext, TrackProvider,
+};
+use crate::{regex, structs::MetadataError};
+
+/// How many YouTube searches run at once while the rest of a collection resolves. Each group
+/// of searches is sent to the queue as soon as it finishes.
+const MAX_CONCURRENT_SEARCHES: usize = 4;
+
+pub struct SpotifyTrackProvider;
+pub struct SpotifyPlaylistProvider;
+pub struct SpotifyAlbumProvider;
+pub struct SpotifyArtistProvider;
+
+/// The id of a Spotify link (`open.spotify.com/album/..`) or URI (`spotify:album:..`) of a kind.
+fn extract_spotify_id<'a>(url: &'a str, kind: &str) -> Option<&'a str> {
+    regex!(r"(?:open\.spotify\.com/(?:intl-[a-z-]+/)?|spotify:)(track|playlist|album|artist)[/:]([a-zA-Z0-9]+)")
+        .captures(url)
+        .filter(|caps| &caps[1] == kind)
+        .and_then(|caps| caps.get(2))
+        .map(|id| id.as_str())
+}
+
+async fn spotify_request(url: &str, ctx: &ResolverContext) -> Result<Value, MetadataError> {
+    let api_key = ctx.spotify_token().await?;
+
+    ctx.reqwest
+        .get(url)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key))
+        .header(CONTENT_TYPE, "application/json")
+        .header(ACCEPT, "application/json")
+        .send()
+        .await
+        .map_err(|_| MetadataError::Network)?
+        .error_for_status()
+        .map_err(|_| MetadataError::NotFound)?
+        .json::<Value>()
+        .await
+        .map_err(|_| MetadataError::Network)
+}
+
+/// The YouTube search query for a Spotify track object.
+fn track_query(track: &Value) -> Option<String> {
+    let name = track.get("name")?.as_str()?;
+    let artist = track.get("artists")?.get(0)?.get("name")?.as_str()?;
+    Some(format!("{} {}", name, artist))
+}
+
+#[async_trait]
+impl TrackProvider for SpotifyTrackProvider {
+    fn name(&self) -> &'static str {
+        "Spotify track"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        extract_spotify_id(query, "track").is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let id = extract_spotify_id(query, "track").ok_or(MetadataError::NotFound)?;
+        if let Some(video) = ctx.cache.get(CacheKey::Spotify(id)).await {
+            return Ok(vec![video].into());
+        }
+
+        let track =
+            spotify_request(&format!("https://api.spotify.com/v1/tracks/{}", id), ctx).await?;
+        let search_query = track_query(&track).ok_or(MetadataError::NotFound)?;
+
+        let video = resolve_query(&search_query, ctx).await?;
+        ctx.cache.insert(CacheKey::Spotify(id), &video).await;
+        Ok(vec![video].into())
+    }
+}
+
+#[async_trait]
+impl TrackProvider for SpotifyPlaylistProvider {
+    fn name(&self) -> &'static str {
+        "Spotify playlist"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        extract_spotify_id(query, "playlist").is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let id = extract_spotify_id(query, "playlist").ok_or(MetadataError::NotFound)?;
+
+        let queries = paged_queries(
+            format!(
+                "https://api.spotify.com/v1/playlists/{}/tracks?limit=100",
+                id
+            ),
+            ctx,
+            Some("track"),
+        )
+        .await?;
+
+        resolve_queries(queries, ctx).await
+    }
+}
+
+#[async_trait]
+impl TrackProvider for SpotifyAlbumProvider {
+    fn name(&self) -> &'static str {
+        "Spotify album"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        extract_spotify_id(query, "album").is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let id = extract_spotify_id(query, "album").ok_or(MetadataError::NotFound)?;
+
+        // Album pages list simplified track objects directly instead of wrapping them.
+        let queries = paged_queries(
+            format!("https://api.spotify.com/v1/albums/{}/tracks?limit=50", id),
+            ctx,
+            None,
+        )
+        .await?;
+
+        resolve_queries(queries, ctx).await
+    }
+}
+
+#[async_trait]
+impl TrackProvider for SpotifyArtistProvider {
+    fn name(&self) -> &'static str {
+        "Spotify artist"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        extract_spotify_id(query, "artist").is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let id = extract_spotify_id(query, "artist").ok_or(MetadataError::NotFound)?;
+
+        let response = spotify_request(
+            &format!(
+                "https://api.spotify.com/v1/artists/{}/top-tracks?market=US",
+                id
+            ),
+            ctx,
+        )
+        .await?;
+
+        let queries = response
+            .get("tracks")
+            .and_then(|tracks| tracks.as_array())
+            .map(|tracks| {
+                tracks
+                    .iter()
+                    .filter_map(track_query)
+                    .take(ctx.playlist_limit)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        resolve_queries(queries, ctx).await
+    }
+}
+
+/// Follows the `next` links of a paged Spotify response and builds a query for every track.
+/// `track_field` is the field of each item holding the track, `None` when items are tracks.
+async fn paged_queries(
+    first_page: String,
+    ctx: &ResolverContext,
+    track_field: Option<&str>,
+) -> Result<Vec<String>, MetadataError> {
+    let mut queries = Vec::new();
+    let mut next_url = Some(first_page);
+
+    while let Some(url) = next_url.filter(|_| queries.len() < ctx.playlist_limit) {
+        let response = spotify_request(&url, ctx).await?;
+
+        if let Some(items) = response.get("items").and_then(|v| v.as_array()) {
+            queries.extend(items.iter().filter_map(|item| match track_field {
+                Some(field) => item.get(field).and_then(track_query),
+                None => track_query(item),
+            }));
+        }
+
+        next_url = response
+            .get("next")
+            .and_then(|n| n.as_str())
+            .map(|s| s.to_string());
+    }
+
+    queries.truncate(ctx.playlist_limit);
+    Ok(queries)
+}
+
+/// Looks the first query up right away so it can start playing, then searches the rest in the
+/// background with bounded concurrency, sending every few tracks on as they resolve.
+async fn resolve_queries(
+    queries: Vec<String>,
+    ctx: &ResolverContext,
+) -> Result<Resolved, MetadataError> {
+    let mut queries = queries.into_iter();
+
+    let mut first = None;
+    let mut skipped = 0;
+    for query in queries.by_ref() {
+        match resolve_query(&query, ctx).await {
+            Ok(video) => {
+                first = Some(video);
+                break;
+            }
+            Err(MetadataError::QuotaExceeded) => return Err(MetadataError::QuotaExceeded),
+            Err(_) => skipped += 1,
+        }
+    }
+    let first = first.ok_or(MetadataError::NotFound)?;
+
+    let remaining: Vec<String> = queries.collect();
+    if remaining.is_empty() {
+        return Ok(Resolved {
+            tracks: vec![first],
+            pending: None,
+            skipped,
+        });
+    }
+
+    let count = remaining.len();
+    let (sender, receiver) = mpsc::channel(4);
+    let ctx = ctx.clone();
+
+    let unresolved = tokio::spawn(async move {
+        let ctx = &ctx;
+        let mut sent = 0;
+        for queries in remaining.chunks(MAX_CONCURRENT_SEARCHES) {
+            let results: Vec<_> = stream::iter(queries)
+                .map(|query| async move {
+                    let id = search_video_id(query, ctx).await?;
+                    Ok::<_, MetadataError>((query.clone(), id))
+                })
+                .buffered(MAX_CONCURRENT_SEARCHES)
+                .collect()
+                .await;
+
+            let quota_exceeded = results
+                .iter()
+                .any(|result| matches!(result, Err(MetadataError::QuotaExceeded)));
+            let found: Vec<(String, String)> = results.into_iter().filter_map(Result::ok).collect();
+            if found.is_empty() {
+                if quota_exceeded {
+                    break;
+                }
+                continue;
+            }
+
+            let ids: Vec<String> = found.iter().map(|(_, id)| id.clone()).collect();
+            let videos = match get_videos(&ids, ctx).await {
+                Ok(videos) if videos.is_empty() => continue,
+                Ok(videos) => videos,
+                Err(MetadataError::QuotaExceeded) => break,
+                Err(_) => continue,
+            };
+            cache_queries(&found, &videos, ctx).await;
+            sent += videos.len();
+            // The receiver is gone once the queue was stopped or the bot left.
+            if sender.send(videos).await.is_err() || quota_exceeded {
+                break;
+            }
+        }
+        count - sent
+    });
+
+    Ok(Resolved {
+        tracks: vec![first],
+        pending: Some(PendingTracks {
+            count,
+            receiver,
+            skipped: unresolved,
+        }),
+        skipped,
+    })
+}
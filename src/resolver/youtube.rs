@@ -0,0 +1,398 @@
+use std::{collections::HashMap, future::Future};
+
+use iso8601_duration::Duration as iso_duration;
+use poise::async_trait;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{cache::CacheKey, ytdlp, Resolved, ResolverContext, TrackProvider};
+use crate::{
+    regex,
+    structs::{
+        MetadataError, ResponsePlaylistApi, ResponseSearchVideoApi, ResponseVideoApi, TrackSource,
+        Video, VideoItem,
+    },
+};
+
+/// The most ids the `videos` endpoint accepts in a single request.
+const MAX_VIDEOS_PER_REQUEST: usize = 50;
+
+pub struct YouTubePlaylistProvider;
+pub struct YouTubeVideoProvider;
+pub struct YouTubeSearchProvider;
+
+fn playlist_id(query: &str) -> Option<&str> {
+    regex!(r"(?:youtube\.com|youtu\.be)/.*[?&]list=((?:PL|LL|EC|UU|FL|RD|UL|TL|PU|OLAK5uy_)[0-9A-Za-z-_]{10,}|RDMM)")
+        .captures(query)
+        .and_then(|caps| caps.get(1))
+        .map(|id| id.as_str())
+}
+
+fn video_id(query: &str) -> Option<&str> {
+    regex!(
+        r"(?:youtube\.com/(?:watch\?(?:.*&)?v=|shorts/|embed/|live/)|youtu\.be/)([0-9A-Za-z_-]{11})"
+    )
+    .captures(query)
+    .and_then(|caps| caps.get(1))
+    .map(|id| id.as_str())
+}
+
+#[async_trait]
+impl TrackProvider for YouTubePlaylistProvider {
+    fn name(&self) -> &'static str {
+        "YouTube playlist"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        playlist_id(query).is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let youtube_id = playlist_id(query).ok_or(MetadataError::NotFound)?;
+
+        with_fallback(
+            ctx,
+            || api_playlist(youtube_id, ctx),
+            || ytdlp::playlist(youtube_id, ctx.playlist_limit),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl TrackProvider for YouTubeVideoProvider {
+    fn name(&self) -> &'static str {
+        "YouTube video"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        video_id(query).is_some()
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        let id = video_id(query).ok_or(MetadataError::NotFound)?;
+        Ok(vec![get_video(id, ctx).await?].into())
+    }
+}
+
+#[async_trait]
+impl TrackProvider for YouTubeSearchProvider {
+    fn name(&self) -> &'static str {
+        "YouTube search"
+    }
+
+    fn matches(&self, _query: &str) -> bool {
+        true
+    }
+
+    async fn resolve(&self, query: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+        Ok(vec![resolve_query(query, ctx).await?].into())
+    }
+}
+
+/// Runs the Data API lookup unless there is no key or the quota ran out, using yt-dlp instead.
+/// Running out of quota during the lookup switches to yt-dlp until the quota resets.
+async fn with_fallback<T, Api, ApiFut, Keyless, KeylessFut>(
+    ctx: &ResolverContext,
+    api: Api,
+    keyless: Keyless,
+) -> Result<T, MetadataError>
+where
+    Api: FnOnce() -> ApiFut,
+    ApiFut: Future<Output = Result<T, MetadataError>>,
+    Keyless: FnOnce() -> KeylessFut,
+    KeylessFut: Future<Output = Result<T, MetadataError>>,
+{
+    if ctx.use_api() {
+        match api().await {
+            Err(MetadataError::QuotaExceeded) => ctx.quota.exhaust(),
+            result => return result,
+        }
+    }
+    keyless().await
+}
+
+async fn api_playlist(youtube_id: &str, ctx: &ResolverContext) -> Result<Resolved, MetadataError> {
+    let mut video_ids: Vec<String> = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut request_playlist_url = format!(
+            "https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet%2CcontentDetails&maxResults={}&playlistId={}&key={}",
+            MAX_VIDEOS_PER_REQUEST,
+            youtube_id,
+            ctx.youtube_api_key
+        );
+        if let Some(page_token) = &page_token {
+            request_playlist_url.push_str(&format!("&pageToken={}", page_token));
+        }
+
+        let page = youtube_request::<ResponsePlaylistApi>(ctx, request_playlist_url).await?;
+        video_ids.extend(
+            page.items
+                .into_iter()
+                .map(|item| item.snippet.resource_id.video_id),
+        );
+
+        page_token = page.next_page_token;
+        if page_token.is_none() || video_ids.len() >= ctx.playlist_limit {
+            break;
+        }
+    }
+    video_ids.truncate(ctx.playlist_limit);
+
+    let mut videos: Vec<Video> = Vec::with_capacity(video_ids.len());
+    for ids in video_ids.chunks(MAX_VIDEOS_PER_REQUEST) {
+        videos.extend(get_videos(ids, ctx).await?);
+    }
+
+    if videos.is_empty() {
+        return Err(MetadataError::NotFound);
+    }
+    Ok(Resolved {
+        skipped: video_ids.len() - videos.len(),
+        tracks: videos,
+        pending: None,
+    })
+}
+
+/// Sends a YouTube Data API request, mapping its error responses to a `MetadataError`.
+async fn youtube_request<T: DeserializeOwned>(
+    ctx: &ResolverContext,
+    url: impl reqwest::IntoUrl,
+) -> Result<T, MetadataError> {
+    let response = ctx
+        .reqwest
+        .get(url)
+        .send()
+        .await
+        .map_err(|_| MetadataError::Network)?;
+
+    let status = response.status();
+    if status.is_success() {
+        return response
+            .json::<T>()
+            .await
+            .map_err(|_| MetadataError::Network);
+    }
+
+    let body = response.json::<Value>().await.unwrap_or_default();
+    let reason = body["error"]["errors"][0]["reason"]
+        .as_str()
+        .unwrap_or_default();
+
+    Err(match (status, reason) {
+        (_, "quotaExceeded" | "dailyLimitExceeded" | "rateLimitExceeded") => {
+            MetadataError::QuotaExceeded
+        }
+        (StatusCode::FORBIDDEN, _) => MetadataError::Private,
+        (StatusCode::NOT_FOUND, _) => MetadataError::NotFound,
+        _ => MetadataError::Network,
+    })
+}
+
+/// Turns a video resource into a playable `Video`, live streams have no fixed duration.
+fn video_from_item(item: VideoItem) -> Result<Video, MetadataError> {
+    if matches!(
+        item.snippet.live_broadcast_content.as_deref(),
+        Some("live" | "upcoming")
+    ) {
+        return Err(MetadataError::LiveStream);
+    }
+
+    let duration = iso_duration::parse(&item.content_details.duration)
+        .ok()
+        .and_then(|duration| duration.to_std())
+        .filter(|duration| !duration.is_zero())
+        .ok_or(MetadataError::LiveStream)?;
+
+    Ok(Video {
+        id: item.id,
+        title: item.snippet.title,
+        duration,
+        source: TrackSource::YouTube,
+    })
+}
+
+async fn get_video(id: &str, ctx: &ResolverContext) -> Result<Video, MetadataError> {
+    if let Some(video) = ctx.cache.get(CacheKey::YouTube(id)).await {
+        return Ok(video);
+    }
+
+    let video = with_fallback(
+        ctx,
+        || api_video(id, ctx),
+        || ytdlp::video(&ytdlp::watch_url(id)),
+    )
+    .await?;
+    ctx.cache.insert(CacheKey::YouTube(id), &video).await;
+    Ok(video)
+}
+
+async fn api_video(id: &str, ctx: &ResolverContext) -> Result<Video, MetadataError> {
+    let request_video_url = format!(
+        "https://youtube.googleapis.com/youtube/v3/videos?part=snippet%2CcontentDetails&id={}&key={}",
+        id,
+        ctx.youtube_api_key
+    );
+
+    let item = youtube_request::<ResponseVideoApi>(ctx, request_video_url)
+        .await?
+        .items
+        .into_iter()
+        .next()
+        .ok_or(MetadataError::NotFound)?;
+    video_from_item(item)
+}
+
+/// Looks up the details of up to 50 videos in one request, only fetching those missing from
+/// the cache. Private, deleted and live videos are left out instead of failing the whole batch.
+pub(super) async fn get_videos(
+    ids: &[String],
+    ctx: &ResolverContext,
+) -> Result<Vec<Video>, MetadataError> {
+    let mut cached = ctx.cache.get_videos(ids).await;
+    let missing: Vec<String> = ids
+        .iter()
+        .filter(|id| !cached.contains_key(*id))
+        .cloned()
+        .collect();
+
+    if !missing.is_empty() {
+        let fetched = with_fallback(
+            ctx,
+            || api_videos(&missing, ctx),
+            || ytdlp::videos(&missing),
+        )
+        .await?;
+        ctx.cache.insert_videos(&fetched).await;
+        cached.extend(fetched.into_iter().map(|video| (video.id.clone(), video)));
+    }
+
+    // Keep the order the ids were given in.
+    Ok(ids
+        .iter()
+        .filter_map(|id| cached.get(id).cloned())
+        .collect())
+}
+
+async fn api_videos(ids: &[String], ctx: &ResolverContext) -> Result<Vec<Video>, MetadataError> {
+    let request_videos_url = format!(
+        "https://youtube.googleapis.com/youtube/v3/videos?part=snippet%2CcontentDetails&id={}&key={}",
+        ids.join(","),
+        ctx.youtube_api_key
+    );
+
+    Ok(youtube_request::<ResponseVideoApi>(ctx, request_videos_url)
+        .await?
+        .items
+        .into_iter()
+        .filter_map(|item| video_from_item(item).ok())
+        .collect())
+}
+
+/// The id of the best YouTube match for the query.
+pub(super) async fn search_video_id(
+    query: &str,
+    ctx: &ResolverContext,
+) -> Result<String, MetadataError> {
+    match ctx.cache.get(CacheKey::Query(query)).await {
+        Some(video) => Ok(video.id),
+        None => find_video_id(query, ctx).await,
+    }
+}
+
+async fn find_video_id(query: &str, ctx: &ResolverContext) -> Result<String, MetadataError> {
+    with_fallback(
+        ctx,
+        || async {
+            api_search_video_ids(query, 1, ctx)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or(MetadataError::NotFound)
+        },
+        || async {
+            ytdlp::search(query, 1)
+                .await?
+                .into_iter()
+                .next()
+                .map(|video| video.id)
+                .ok_or(MetadataError::NotFound)
+        },
+    )
+    .await
+}
+
+async fn api_search_video_ids(
+    query: &str,
+    limit: usize,
+    ctx: &ResolverContext,
+) -> Result<Vec<String>, MetadataError> {
+    let url = format!(
+        "https://youtube.googleapis.com/youtube/v3/search?part=snippet&order=relevance&type=video&maxResults={}&q={}&key={}", 
+        limit,
+        urlencoding::encode(query),
+        ctx.youtube_api_key
+    );
+    Ok(youtube_request::<ResponseSearchVideoApi>(ctx, url)
+        .await?
+        .items
+        .into_iter()
+        .map(|item| item.id.video_id)
+        .collect())
+}
+
+/// The best matches for the query with their details, most relevant first.
+pub(super) async fn search_videos(
+    query: &str,
+    limit: usize,
+    ctx: &ResolverContext,
+) -> Result<Vec<Video>, MetadataError> {
+    with_fallback(
+        ctx,
+        || async {
+            let ids = api_search_video_ids(query, limit, ctx).await?;
+            let videos = get_videos(&ids, ctx).await?;
+            if videos.is_empty() {
+                return Err(MetadataError::NotFound);
+            }
+            Ok(videos)
+        },
+        || ytdlp::search(query, limit),
+    )
+    .await
+}
+
+/// Searches YouTube for the query and looks up the details of the best match.
+pub(super) async fn resolve_query(
+    query: &str,
+    ctx: &ResolverContext,
+) -> Result<Video, MetadataError> {
+    if let Some(video) = ctx.cache.get(CacheKey::Query(query)).await {
+        return Ok(video);
+    }
+
+    let id = find_video_id(query, ctx).await?;
+    let video = get_video(&id, ctx).await?;
+    ctx.cache.insert(CacheKey::Query(query), &video).await;
+    Ok(video)
+}
+
+/// Caches which video each query found, for the queries whose video was looked up.
+pub(super) async fn cache_queries(
+    queries: &[(String, String)],
+    videos: &[Video],
+    ctx: &ResolverContext,
+) {
+    let videos: HashMap<&str, &Video> = videos
+        .iter()
+        .map(|video| (video.id.as_str(), video))
+        .collect();
+
+    for (query, id) in queries {
+        if let Some(video) = videos.get(id.as_str()) {
+            ctx.cache.insert(CacheKey::Query(query), video).await;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{process::Stdio, time::Duration};
+
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use tokio::process::Command;
+
+use super::Resolved;
+use crate::structs::{MetadataError, TrackSource, Video};
+
+const YT_DLP: &str = "yt-dlp";
+
+/// How many yt-dlp processes run at once when looking up a batch of videos.
+const MAX_CONCURRENT_PROCESSES: usize = 4;
+/// How long a yt-dlp process may take before it is killed, it can hang on a stalled connection.
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct Entry {
+    id: String,
+    title: Option<String>,
+    duration: Option<f64>,
+    live_status: Option<String>,
+    availability: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Playlist {
+    #[serde(default)]
+    entries: Vec<Option<Entry>>,
+}
+
+impl Entry {
+    fn into_video(self) -> Result<Video, MetadataError> {
+        if matches!(self.live_status.as_deref(), Some("is_live" | "is_upcoming")) {
+            return Err(MetadataError::LiveStream);
+        }
+        if matches!(
+            self.availability.as_deref(),
+            Some("private" | "premium_only" | "subscriber_only" | "needs_auth")
+        ) {
+            return Err(MetadataError::Private);
+        }
+
+        // Private and deleted playlist entries come without a duration.
+        let duration = self
+            .duration
+            .filter(|duration| *duration > 0.0)
+            .map(Duration::from_secs_f64)
+            .ok_or(MetadataError::NotFound)?;
+
+        Ok(Video {
+            id: self.id,
+            title: self.title.ok_or(MetadataError::NotFound)?,
+            duration,
+            source: TrackSource::YouTube,
+        })
+    }
+}
+
+pub(super) fn watch_url(id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={}", id)
+}
+
+async fn run(args: &[&str]) -> Result<Vec<u8>, MetadataError> {
+    let child = Command::new(YT_DLP)
+        .args(["--ignore-config", "--no-warnings", "--skip-download"])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|_| MetadataError::Network)?;
+
+    // Dropping the child on a timeout kills it.
+    let output = tokio::time::timeout(PROCESS_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| MetadataError::Network)?
+        .map_err(|_| MetadataError::Network)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(if stderr.contains("Private video") {
+            MetadataError::Private
+        } else if stderr.contains("live event") {
+            MetadataError::LiveStream
+        } else if stderr.contains("unavailable") || stderr.contains("does not exist") {
+            MetadataError::NotFound
+        } else {
+            MetadataError::Network
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+fn parse_lines(output: &[u8]) -> Vec<Entry> {
+    output
+        .split(|&b| b == b'\n')
+        .filter_map(|line| serde_json::from_slice(line).ok())
+        .collect()
+}
+
+/// Full details of a single video, `url` can also be a `ytsearch1:` query.
+pub(super) async fn video(url: &str) -> Result<Video, MetadataError> {
+    let output = run(&["-j", "--no-playlist", url]).await?;
+
+    parse_lines(&output)
+        .into_iter()
+        .next()
+        .ok_or(MetadataError::NotFound)?
+        .into_video()
+}
+
+/// The direct url of the best audio stream of a video, for playing it through ffmpeg.
+pub async fn stream_url(id: &str) -> Result<String, MetadataError> {
+    let output = run(&[
+        "-f",
+        "bestaudio/best",
+        "-g",
+        "--no-playlist",
+        &watch_url(id),
+    ])
+    .await?;
+
+    String::from_utf8_lossy(&output)
+        .lines()
+        .next()
+        .map(str::to_string)
+        .ok_or(MetadataError::NotFound)
+}
+
+/// Details of several videos, leaving out the ones that can't be played.
+pub(super) async fn videos(ids: &[String]) -> Result<Vec<Video>, MetadataError> {
+    Ok(stream::iter(ids)
+        .map(|id| async move { video(&watch_url(id)).await })
+        .buffered(MAX_CONCURRENT_PROCESSES)
+        .filter_map(|video| async move { video.ok() })
+        .collect()
+        .await)
+}
+
+pub(super) async fn search(query: &str, limit: usize) -> Result<Vec<Video>, MetadataError> {
+    let output = run(&[
+        "-j",
+        "--flat-playlist",
+        &format!("ytsearch{}:{}", limit, query),
+    ])
+    .await?;
+
+    let videos: Vec<Video> = parse_lines(&output)
+        .into_iter()
+        .filter_map(|entry| entry.into_video().ok())
+        .collect();
+
+    if videos.is_empty() {
+        return Err(MetadataError::NotFound);
+    }
+    Ok(videos)
+}
+
+pub(super) async fn playlist(id: &str, limit: usize) -> Result<Resolved, MetadataError> {
+    let output = run(&[
+        "-J",
+        "--flat-playlist",
+        "--playlist-end",
+        &limit.to_string(),
+        &format!("https://www.youtube.com/playlist?list={}", id),
+    ])
+    .await?;
+
+    let playlist: Playlist =
+        serde_json::from_slice(&output).map_err(|_| MetadataError::NotFound)?;
+    let total = playlist.entries.len();
+    let videos: Vec<Video> = playlist
+        .entries
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.into_video().ok())
+        .collect();
+
+    if videos.is_empty() {
+        return Err(MetadataError::NotFound);
+    }
+    Ok(Resolved {
+        skipped: total - videos.len(),
+        tracks: videos,
+        pending: None,
+    })
+}
@@ -1,32 +1,60 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::{Duration, Instant},
+};
 
 use bson::doc;
 use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
 use mongodb::Database;
 use poise::{
     async_trait,
-    serenity_prelude::{ChannelId, FullEvent, Http},
+    serenity_prelude::{ChannelId, FullEvent, GuildId, Http, UserId},
 };
 use serde::{Deserialize, Serialize};
 use serenity::{all::EventHandler, prelude::Context};
-use songbird::{Event, EventContext, EventHandler as SongbirdEventHandler};
+use songbird::{
+    tracks::PlayMode, Event, EventContext, EventHandler as SongbirdEventHandler, Songbird,
+};
+use tokio::sync::{mpsc, RwLock};
+use uuid::Uuid;
 
-use crate::utils::{reminder_checker, Valeriyya};
+use crate::{
+    appeals::handle_appeal_interaction,
+    filters::AudioFilter,
+    resolver::{guarded_client, is_public_link},
+    utils::{enqueue_video, reminder_checker, restore_queues, Valeriyya},
+    Context as PoiseContext, Data,
+};
 
 pub struct ValeriyyaEventHandler {
-    pub database: Database,
+    pub data: Arc<Data>,
 }
 
 #[async_trait]
 impl EventHandler for ValeriyyaEventHandler {
     async fn dispatch(&self, ctx: &Context, event: &FullEvent) {
         match event {
+            FullEvent::InteractionCreate { interaction, .. } => {
+                handle_appeal_interaction(ctx, &self.data, interaction).await;
+            }
+            FullEvent::VoiceStateUpdate { new, .. } => {
+                if let Some(guild_id) = new.guild_id {
+                    self.data.inactivity.notify(guild_id);
+                }
+            }
             FullEvent::Ready { data_about_bot, .. } => {
                 let ctx = ctx.clone();
-                let database = self.database.clone();
+                let data = self.data.clone();
 
+                tokio::spawn(restore_queues(ctx.clone(), data.clone()));
                 tokio::spawn(async move {
-                    reminder_checker(ctx.into(), database).await;
+                    reminder_checker(ctx.into(), data).await;
                 });
                 tracing::info!("{} is connected!", data_about_bot.user.name);
             }
@@ -35,15 +63,55 @@ impl EventHandler for ValeriyyaEventHandler {
     }
 }
 
+pub const DEFAULT_PREFIX: &str = "!";
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct GuildDbChannels {
     pub logs: Option<String>,
     pub welcome: Option<String>,
     pub starboard: Option<String>,
+    pub appeals: Option<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct GuildDbRoles {
     pub staff: Option<String>,
+    #[serde(default)]
+    pub dj: Option<String>,
+    #[serde(default)]
+    pub grants: Vec<RoleGrant>,
+}
+
+pub const DEFAULT_VOLUME: u16 = 100;
+pub const DEFAULT_VOTE_SKIP: u8 = 50;
+pub const DEFAULT_PLAYLIST_LIMIT: usize = 200;
+/// Minutes the bot stays in an idle or empty voice channel before leaving.
+pub const DEFAULT_IDLE_TIMEOUT: u64 = 5;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct GuildDbMusic {
+    pub volume: Option<u16>,
+    #[serde(default)]
+    pub vote_skip: Option<u8>,
+    #[serde(default)]
+    pub playlist_limit: Option<usize>,
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, poise::ChoiceParameter)]
+pub enum CommandCategory {
+    #[name = "moderation"]
+    Moderation,
+    #[name = "settings"]
+    Settings,
+    #[name = "dj"]
+    Dj,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct RoleGrant {
+    pub role: String,
+    pub category: CommandCategory,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
@@ -62,6 +130,7 @@ pub enum ActionTypes {
     Unban,
     Kick,
     Mute,
+    Unmute,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -78,6 +147,25 @@ pub struct Case {
     pub message: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum AppealStatus {
+    Pending,
+    Accepted,
+    Denied,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Appeal {
+    pub id: u32,
+    pub case_id: u32,
+    pub user_id: String,
+    pub reason: String,
+    pub status: AppealStatus,
+    pub date: i64,
+    pub message: Option<String>,
+    pub reviewer_id: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct History {
     pub id: String,
@@ -96,6 +184,14 @@ pub struct GuildDb {
     pub roles: GuildDbRoles,
     pub reminders: Vec<Reminder>,
     pub reminder_count: u32,
+    #[serde(default)]
+    pub appeals: Vec<Appeal>,
+    #[serde(default)]
+    pub appeals_number: u32,
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub music: GuildDbMusic,
 }
 
 impl GuildDb {
@@ -142,6 +238,35 @@ impl GuildDb {
         self
     }
 
+    #[inline(always)]
+    pub fn add_appeal(mut self, appeal: Appeal) -> Self {
+        self.appeals_number += 1;
+        self.appeals.push(appeal);
+        self
+    }
+
+    #[inline(always)]
+    pub fn update_appeal(
+        mut self,
+        appeal_id: u32,
+        status: AppealStatus,
+        reviewer_id: Option<String>,
+    ) -> Self {
+        if let Some(appeal) = self.appeals.iter_mut().find(|a| a.id == appeal_id) {
+            appeal.status = status;
+            appeal.reviewer_id = reviewer_id;
+        }
+        self
+    }
+
+    #[inline(always)]
+    pub fn set_appeal_message(mut self, appeal_id: u32, message: Option<String>) -> Self {
+        if let Some(appeal) = self.appeals.iter_mut().find(|a| a.id == appeal_id) {
+            appeal.message = message;
+        }
+        self
+    }
+
     #[inline(always)]
     pub fn add_reminder(mut self, reminder: Reminder) -> Self {
         self.reminders.push(reminder);
@@ -186,6 +311,28 @@ impl GuildDb {
         self
     }
 
+    /// Stores the prefixes longest first, so matching takes `!!` before `!` shadows it.
+    #[inline(always)]
+    pub fn set_prefixes(mut self, mut prefixes: Vec<String>) -> Self {
+        prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.len()));
+        self.prefixes = prefixes;
+        self
+    }
+
+    pub fn get_prefixes(&self) -> Vec<String> {
+        if self.prefixes.is_empty() {
+            vec![DEFAULT_PREFIX.to_string()]
+        } else {
+            self.prefixes.clone()
+        }
+    }
+
+    #[inline(always)]
+    pub fn set_music(mut self, music: GuildDbMusic) -> Self {
+        self.music = music;
+        self
+    }
+
     #[inline(always)]
     pub fn set_roles(mut self, roles: GuildDbRoles) -> Self {
         self.roles = roles;
@@ -231,17 +378,136 @@ impl GuildDb {
             .collect()
     }
 
-    pub async fn execute(self, database: &Database) -> Self {
-        let db = database.collection::<GuildDb>("guild");
-        db.find_one_and_update(
-            doc! { "gid": self.gid.clone() },
-            doc! {
-                "$set": bson::to_document(&self).unwrap()
-            },
-        )
-        .await
-        .unwrap()
-        .unwrap()
+    pub async fn execute(self, data: &Data) -> Self {
+        let db = data.database().collection::<GuildDb>("guild");
+        let guild = db
+            .find_one_and_update(
+                doc! { "gid": self.gid.clone() },
+                doc! {
+                    "$set": bson::to_document(&self).unwrap()
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        if let Ok(guild_id) = self.gid.parse::<u64>() {
+            data.guild_cache.invalidate(guild_id).await;
+        }
+        guild
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GuildCacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl GuildCacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64 * 100.0
+    }
+}
+
+/// Bounded cache of guild settings so commands and the reminder loop don't query MongoDB every time.
+/// Entries expire after the ttl and every write done through [`GuildDb::execute`] invalidates them.
+#[derive(Debug)]
+pub struct GuildCache {
+    entries: RwLock<HashMap<u64, (Instant, GuildDb)>>,
+    /// Bumped by every invalidation, so a read that started before a write can't cache what it
+    /// loaded after the write invalidated the guild.
+    generations: StdMutex<HashMap<u64, u64>>,
+    capacity: usize,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl GuildCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::with_capacity(capacity)),
+            generations: StdMutex::new(HashMap::new()),
+            capacity,
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn get(&self, guild_id: u64) -> Option<GuildDb> {
+        let cached = self
+            .entries
+            .read()
+            .await
+            .get(&guild_id)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, guild)| guild.clone());
+
+        match cached {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        cached
+    }
+
+    /// Taken before loading a guild that missed the cache, to be passed on to `insert`.
+    pub fn generation(&self, guild_id: u64) -> u64 {
+        self.generations
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Caches the guild unless it was invalidated since `generation` was taken.
+    pub async fn insert(&self, guild_id: u64, guild: GuildDb, generation: u64) {
+        let mut entries = self.entries.write().await;
+        if self.generation(guild_id) != generation {
+            return;
+        }
+
+        if entries.len() >= self.capacity && !entries.contains_key(&guild_id) {
+            entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (inserted, _))| *inserted)
+                    .map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(guild_id, (Instant::now(), guild));
+    }
+
+    pub async fn invalidate(&self, guild_id: u64) {
+        let mut entries = self.entries.write().await;
+        *self
+            .generations
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_default() += 1;
+        entries.remove(&guild_id);
+    }
+
+    pub async fn stats(&self) -> GuildCacheStats {
+        GuildCacheStats {
+            entries: self.entries.read().await.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
     }
 }
 
@@ -263,6 +529,57 @@ impl GuildDbChannels {
         self.starboard = starboard;
         self
     }
+
+    #[inline(always)]
+    pub fn set_appeals_channel(mut self, appeals: Option<String>) -> Self {
+        self.appeals = appeals;
+        self
+    }
+}
+
+impl GuildDbMusic {
+    #[inline(always)]
+    pub fn set_volume(mut self, volume: Option<u16>) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// The volume new tracks start with, as the multiplier songbird expects.
+    pub fn track_volume(&self) -> f32 {
+        f32::from(self.volume.unwrap_or(DEFAULT_VOLUME)) / 100.0
+    }
+
+    #[inline(always)]
+    pub fn set_vote_skip(mut self, vote_skip: Option<u8>) -> Self {
+        self.vote_skip = vote_skip;
+        self
+    }
+
+    #[inline(always)]
+    pub fn set_playlist_limit(mut self, playlist_limit: Option<usize>) -> Self {
+        self.playlist_limit = playlist_limit;
+        self
+    }
+
+    pub fn playlist_limit(&self) -> usize {
+        self.playlist_limit.unwrap_or(DEFAULT_PLAYLIST_LIMIT)
+    }
+
+    #[inline(always)]
+    pub fn set_idle_timeout(mut self, idle_timeout: Option<u64>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout.unwrap_or(DEFAULT_IDLE_TIMEOUT) * 60)
+    }
+
+    /// Votes needed to skip a song with this many listeners in the channel.
+    pub fn votes_needed(&self, listeners: usize) -> usize {
+        let percentage = usize::from(self.vote_skip.unwrap_or(DEFAULT_VOTE_SKIP));
+        (listeners * percentage).div_ceil(100).max(1)
+    }
 }
 
 impl GuildDbRoles {
@@ -271,6 +588,47 @@ impl GuildDbRoles {
         self.staff = staff;
         self
     }
+
+    #[inline(always)]
+    pub fn set_dj_role(mut self, dj: Option<String>) -> Self {
+        self.dj = dj;
+        self
+    }
+
+    #[inline(always)]
+    pub fn add_grant(mut self, grant: RoleGrant) -> Self {
+        if !self.grants.contains(&grant) {
+            self.grants.push(grant);
+        }
+        self
+    }
+
+    #[inline(always)]
+    pub fn remove_grant(mut self, grant: &RoleGrant) -> Self {
+        self.grants.retain(|g| g != grant);
+        self
+    }
+
+    /// Roles allowed to use the commands of a category, the staff role always counts for moderation
+    /// and the DJ role for music.
+    pub fn granted_roles(&self, category: CommandCategory) -> Vec<String> {
+        let mut roles: Vec<String> = self
+            .grants
+            .iter()
+            .filter(|grant| grant.category == category)
+            .map(|grant| grant.role.clone())
+            .collect();
+
+        let implied = match category {
+            CommandCategory::Moderation => &self.staff,
+            CommandCategory::Dj => &self.dj,
+            CommandCategory::Settings => &None,
+        };
+        if let Some(role) = implied {
+            roles.push(role.clone());
+        }
+        roles
+    }
 }
 
 pub enum CaseUpdateAction {
@@ -310,6 +668,8 @@ pub struct VideoItem {
 #[derive(Deserialize, Debug, Clone)]
 pub struct VideoSnippet {
     pub title: String,
+    #[serde(rename = "liveBroadcastContent", default)]
+    pub live_broadcast_content: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -344,6 +704,46 @@ pub struct PlaylistItem {
 #[derive(Deserialize, Debug, Clone)]
 pub struct ResponsePlaylistApi {
     pub items: Vec<PlaylistItem>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+/// Why a query or link could not be resolved to playable videos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataError {
+    NotFound,
+    QuotaExceeded,
+    Private,
+    LiveStream,
+    Network,
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::NotFound => write!(f, "I couldn't find any songs for that query."),
+            MetadataError::QuotaExceeded => write!(
+                f,
+                "The YouTube quota for today has been used up, please try again later."
+            ),
+            MetadataError::Private => write!(f, "That video or playlist is private."),
+            MetadataError::LiveStream => write!(f, "Live streams can't be played."),
+            MetadataError::Network => write!(
+                f,
+                "I couldn't reach YouTube or Spotify, please try again in a moment."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+/// Where a track is streamed from, `Video::id` is a YouTube id or a direct URL accordingly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrackSource {
+    #[default]
+    YouTube,
+    Http,
 }
 
 #[derive(Clone, Debug)]
@@ -351,6 +751,193 @@ pub struct Video {
     pub id: String,
     pub title: String,
     pub duration: std::time::Duration,
+    pub source: TrackSource,
+}
+
+impl Video {
+    pub fn url(&self) -> String {
+        match self.source {
+            TrackSource::YouTube => format!("https://youtu.be/{}", self.id),
+            TrackSource::Http => self.id.clone(),
+        }
+    }
+
+    pub fn thumbnail(&self) -> Option<String> {
+        match self.source {
+            TrackSource::YouTube => {
+                Some(format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", self.id))
+            }
+            TrackSource::Http => None,
+        }
+    }
+}
+
+/// Data attached to every songbird track, read back through `TrackHandle::data`.
+#[derive(Clone, Debug)]
+pub struct TrackMetadata {
+    pub video: Video,
+    pub requester: UserId,
+    /// How far into the song the track starts, set when it is restarted with a new filter.
+    pub start: Duration,
+    /// How fast the track plays the song, the position of the track is scaled by it.
+    pub speed: f64,
+}
+
+impl TrackMetadata {
+    pub fn new(video: Video, requester: UserId) -> Self {
+        Self {
+            video,
+            requester,
+            start: Duration::ZERO,
+            speed: 1.0,
+        }
+    }
+
+    /// The position in the song, given the position of the track playing it.
+    pub fn position(&self, track_position: Duration) -> Duration {
+        self.start + track_position.mul_f64(self.speed)
+    }
+}
+
+/// The most songs a saved playlist holds.
+pub const MAX_PLAYLIST_TRACKS: usize = 500;
+
+/// Who a saved playlist belongs to, personal playlists follow the member across guilds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistOwner {
+    User(UserId),
+    Guild(GuildId),
+}
+
+impl PlaylistOwner {
+    fn key(&self) -> String {
+        match self {
+            Self::User(user_id) => format!("user:{}", user_id),
+            Self::Guild(guild_id) => format!("guild:{}", guild_id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlaylistTrack {
+    pub id: String,
+    pub title: String,
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub source: TrackSource,
+}
+
+impl From<&Video> for PlaylistTrack {
+    fn from(video: &Video) -> Self {
+        Self {
+            id: video.id.clone(),
+            title: video.title.clone(),
+            duration_ms: video.duration.as_millis() as u64,
+            source: video.source,
+        }
+    }
+}
+
+impl PlaylistTrack {
+    /// Stored links are checked again before playing, they may predate the address checks or
+    /// have been written to the database directly.
+    pub fn is_playable(&self) -> bool {
+        match self.source {
+            TrackSource::YouTube => true,
+            TrackSource::Http => is_public_link(&self.id),
+        }
+    }
+}
+
+impl From<PlaylistTrack> for Video {
+    fn from(track: PlaylistTrack) -> Self {
+        Self {
+            id: track.id,
+            title: track.title,
+            duration: Duration::from_millis(track.duration_ms),
+            source: track.source,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedPlaylist {
+    pub owner: String,
+    pub name: String,
+    pub tracks: Vec<PlaylistTrack>,
+}
+
+impl SavedPlaylist {
+    pub fn new(owner: PlaylistOwner, name: impl Into<String>) -> Self {
+        Self {
+            owner: owner.key(),
+            name: name.into(),
+            tracks: Vec::new(),
+        }
+    }
+
+    fn collection(data: &Data) -> mongodb::Collection<SavedPlaylist> {
+        data.database().collection::<SavedPlaylist>("playlists")
+    }
+
+    pub async fn find(
+        data: &Data,
+        owner: PlaylistOwner,
+        name: &str,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        Self::collection(data)
+            .find_one(doc! { "owner": owner.key(), "name": name })
+            .await
+    }
+
+    pub async fn list(
+        data: &Data,
+        owner: PlaylistOwner,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        Self::collection(data)
+            .find(doc! { "owner": owner.key() })
+            .sort(doc! { "name": 1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Creates the playlist or replaces the one with the same name.
+    pub async fn save(&self, data: &Data) -> Result<(), mongodb::error::Error> {
+        Self::collection(data)
+            .replace_one(doc! { "owner": &self.owner, "name": &self.name }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(
+        data: &Data,
+        owner: PlaylistOwner,
+        name: &str,
+    ) -> Result<bool, mongodb::error::Error> {
+        let result = Self::collection(data)
+            .delete_one(doc! { "owner": owner.key(), "name": name })
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.tracks
+            .iter()
+            .map(|track| Duration::from_millis(track.duration_ms))
+            .sum()
+    }
+
+    /// The songs of the playlist that can still be played.
+    pub fn videos(&self) -> Vec<Video> {
+        self.tracks
+            .iter()
+            .filter(|track| track.is_playable())
+            .cloned()
+            .map(Video::from)
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -371,31 +958,499 @@ struct Album {
     pub external_urls: ExternalUrls,
 }
 
-pub enum SpotifyQuery {
-    Track(String),
-    Playlist(Vec<String>),
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    Track,
+    TrackTimes(usize),
+    Queue,
 }
 
-pub struct SongEndNotifier {
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopMode::Off => write!(f, "Off"),
+            LoopMode::Track => write!(f, "Track"),
+            LoopMode::TrackTimes(times) => write!(f, "Track ({} times)", times),
+            LoopMode::Queue => write!(f, "Queue"),
+        }
+    }
+}
+
+impl LoopMode {
+    /// The mode once a song has finished, a repeat count is used up by then.
+    pub fn after_song_end(self) -> Self {
+        match self {
+            LoopMode::TrackTimes(_) => LoopMode::Off,
+            mode => mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkipVote {
+    pub track: Uuid,
+    pub voters: HashSet<UserId>,
+}
+
+/// Playback state that lives as long as the bot is connected in a guild.
+#[derive(Debug, Default, Clone)]
+pub struct MusicSession {
+    pub loop_mode: LoopMode,
+    pub skip_vote: Option<SkipVote>,
+    /// Where the queue was last started from, announcements after a restart go here.
+    pub text_channel: Option<ChannelId>,
+    pub filter: AudioFilter,
+    /// Tracks stopped to restart their song with a new filter, they end without announcing it.
+    pub replaced: HashSet<Uuid>,
+    /// When the song `previous` last went back to was played, so using it again goes further
+    /// back. Cleared once a song finishes.
+    pub history_cursor: Option<bson::DateTime>,
+    /// Counts how often the queue was stopped or cleared, songs still loading in the background
+    /// are dropped once it changes.
+    pub clears: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct MusicSessions {
+    sessions: RwLock<HashMap<u64, MusicSession>>,
+}
+
+impl MusicSessions {
+    pub async fn get(&self, guild_id: u64) -> MusicSession {
+        self.sessions
+            .read()
+            .await
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn update(&self, guild_id: u64, update: impl FnOnce(&mut MusicSession)) {
+        update(self.sessions.write().await.entry(guild_id).or_default());
+    }
+
+    pub async fn remove(&self, guild_id: u64) {
+        self.sessions.write().await.remove(&guild_id);
+    }
+}
+
+/// How often a watcher checks the call on its own, in case an event was missed.
+const IDLE_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Watches every guild the bot is connected in and leaves once the queue stayed empty, or the
+/// channel had no listeners, for the guild's idle timeout.
+#[derive(Debug, Default)]
+pub struct InactivityManager {
+    watchers: StdMutex<HashMap<u64, mpsc::UnboundedSender<()>>>,
+}
+
+impl InactivityManager {
+    /// Starts watching the guild's call unless it is watched already.
+    pub fn watch(&self, ctx: &Context, guild_id: GuildId) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(sender) = watchers.get(&guild_id.get()) {
+            if sender.send(()).is_ok() {
+                return;
+            }
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        watchers.insert(guild_id.get(), sender);
+        tokio::spawn(watch_guild(ctx.clone(), guild_id, receiver));
+    }
+
+    /// Makes the guild's watcher check again whether the bot is idle.
+    pub fn notify(&self, guild_id: GuildId) {
+        if let Some(sender) = self.watchers.lock().unwrap().get(&guild_id.get()) {
+            let _ = sender.send(());
+        }
+    }
+}
+
+async fn watch_guild(ctx: Context, guild_id: GuildId, mut changes: mpsc::UnboundedReceiver<()>) {
+    let data = ctx.data::<Data>();
+    let mut idle_since: Option<Instant> = None;
+
+    loop {
+        let wait = match is_idle(&ctx, &data, guild_id).await {
+            None => break,
+            Some(false) => {
+                idle_since = None;
+                IDLE_RECHECK_INTERVAL
+            }
+            Some(true) => {
+                let since = *idle_since.get_or_insert_with(Instant::now);
+                let timeout = Valeriyya::get_database(&data, guild_id.get())
+                    .await
+                    .map_or(Duration::from_secs(DEFAULT_IDLE_TIMEOUT * 60), |db| {
+                        db.music.idle_timeout()
+                    });
+
+                let remaining = timeout.saturating_sub(since.elapsed());
+                if remaining.is_zero() {
+                    leave_idle(&ctx, &data, guild_id).await;
+                    break;
+                }
+                remaining.min(IDLE_RECHECK_INTERVAL)
+            }
+        };
+
+        if let Ok(None) = tokio::time::timeout(wait, changes.recv()).await {
+            break;
+        }
+    }
+}
+
+/// Whether the call has nothing to play or nobody to play for, `None` once the bot left.
+async fn is_idle(ctx: &Context, data: &Data, guild_id: GuildId) -> Option<bool> {
+    let handler_lock = data.songbird.get(guild_id)?;
+    let (channel, queue_empty) = {
+        let handler = handler_lock.lock().await;
+        (handler.current_channel(), handler.queue().is_empty())
+    };
+
+    let Some(channel) = channel else {
+        return Some(true);
+    };
+    if queue_empty {
+        return Some(true);
+    }
+
+    let channel_id = ChannelId::new(channel.0.get());
+    let bot_id = ctx.cache.current_user().id;
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return Some(false);
+    };
+    let listeners = guild
+        .voice_states
+        .iter()
+        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+        .filter(|voice_state| {
+            voice_state
+                .member
+                .as_ref()
+                .map_or(voice_state.user_id != bot_id, |member| !member.user.bot())
+        })
+        .count();
+
+    Some(listeners == 0)
+}
+
+async fn leave_idle(ctx: &Context, data: &Data, guild_id: GuildId) {
+    let _ = data.songbird.remove(guild_id).await;
+    let session = data.music_sessions.get(guild_id.get()).await;
+    data.music_sessions.remove(guild_id.get()).await;
+    let _ = SavedQueue::delete(data, guild_id.get()).await;
+
+    if let Some(channel_id) = session.text_channel {
+        let _ = channel_id
+            .widen()
+            .send_message(
+                &ctx.http,
+                Valeriyya::msg_reply().add_embed(
+                    Valeriyya::embed()
+                        .description(
+                            "I left the voice channel since nothing was playing or nobody was listening.",
+                        )
+                        .title("Left the channel"),
+                ),
+            )
+            .await;
+    }
+}
+
+/// How often the queues are saved so they can be restored after a restart.
+pub const QUEUE_SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedQueueTrack {
+    #[serde(flatten)]
+    pub track: PlaylistTrack,
+    pub requester: u64,
+}
+
+/// A guild's queue as it was when last saved, restored when the bot is ready again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedQueue {
+    #[serde(rename = "_id")]
+    pub guild_id: u64,
+    pub voice_channel: u64,
+    pub text_channel: u64,
+    pub tracks: Vec<SavedQueueTrack>,
+    /// How far into the first track playback was.
+    pub position_ms: u64,
+    pub loop_mode: LoopMode,
+}
+
+impl SavedQueue {
+    fn collection(data: &Data) -> mongodb::Collection<SavedQueue> {
+        data.database().collection::<SavedQueue>("queues")
+    }
+
+    pub async fn all(data: &Data) -> Result<Vec<Self>, mongodb::error::Error> {
+        Self::collection(data)
+            .find(doc! {})
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn save(&self, data: &Data) -> Result<(), mongodb::error::Error> {
+        Self::collection(data)
+            .replace_one(doc! { "_id": self.guild_id as i64 }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(data: &Data, guild_id: u64) -> Result<(), mongodb::error::Error> {
+        Self::collection(data)
+            .delete_one(doc! { "_id": guild_id as i64 })
+            .await?;
+        Ok(())
+    }
+}
+
+/// The most songs the history of a guild keeps.
+pub const MAX_HISTORY: usize = 50;
+
+/// A song a guild played, kept until `MAX_HISTORY` newer songs were played.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub guild_id: u64,
+    #[serde(flatten)]
+    pub track: PlaylistTrack,
+    pub requester: u64,
+    pub played_at: bson::DateTime,
+}
+
+/// How often a guild played a song, for its most played leaderboard.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayCount {
+    pub guild_id: u64,
+    #[serde(flatten)]
+    pub track: PlaylistTrack,
+    pub plays: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(guild_id: u64, metadata: &TrackMetadata) -> Self {
+        Self {
+            guild_id,
+            track: PlaylistTrack::from(&metadata.video),
+            requester: metadata.requester.get(),
+            played_at: bson::DateTime::now(),
+        }
+    }
+
+    fn collection(db: &Database) -> mongodb::Collection<HistoryEntry> {
+        db.collection::<HistoryEntry>("history")
+    }
+
+    /// Adds the song to the history, dropping the songs past `MAX_HISTORY`, and counts the play.
+    pub async fn record(&self, db: &Database) -> Result<(), mongodb::error::Error> {
+        let history = Self::collection(db);
+        history.insert_one(self).await?;
+
+        let guild_id = self.guild_id as i64;
+        let oldest_kept = history
+            .find(doc! { "guild_id": guild_id })
+            .sort(doc! { "played_at": -1 })
+            .skip(MAX_HISTORY as u64 - 1)
+            .limit(1)
+            .await?
+            .try_next()
+            .await?;
+        if let Some(oldest_kept) = oldest_kept {
+            history
+                .delete_many(doc! {
+                    "guild_id": guild_id,
+                    "played_at": { "$lt": oldest_kept.played_at },
+                })
+                .await?;
+        }
+
+        PlayCount::collection(db)
+            .update_one(
+                doc! { "guild_id": guild_id, "id": self.track.id.as_str() },
+                doc! {
+                    "$inc": { "plays": 1_i64 },
+                    "$set": {
+                        "title": self.track.title.as_str(),
+                        "duration_ms": self.track.duration_ms as i64,
+                        "source": bson::to_bson(&self.track.source).unwrap_or_default(),
+                    },
+                },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// The songs a guild played, newest first.
+    pub async fn recent(
+        db: &Database,
+        guild_id: u64,
+        limit: usize,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        Self::collection(db)
+            .find(doc! { "guild_id": guild_id as i64 })
+            .sort(doc! { "played_at": -1 })
+            .limit(limit as i64)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// The newest song played before `before`, or the newest song at all.
+    pub async fn before(
+        db: &Database,
+        guild_id: u64,
+        before: Option<bson::DateTime>,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        let mut filter = doc! { "guild_id": guild_id as i64 };
+        if let Some(before) = before {
+            filter.insert("played_at", doc! { "$lt": before });
+        }
+
+        Self::collection(db)
+            .find_one(filter)
+            .sort(doc! { "played_at": -1 })
+            .await
+    }
+}
+
+impl PlayCount {
+    fn collection(db: &Database) -> mongodb::Collection<PlayCount> {
+        db.collection::<PlayCount>("play_counts")
+    }
+
+    /// The songs a guild played the most, most played first.
+    pub async fn top(
+        db: &Database,
+        guild_id: u64,
+        limit: usize,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        Self::collection(db)
+            .find(doc! { "guild_id": guild_id as i64 })
+            .sort(doc! { "plays": -1, "title": 1 })
+            .limit(limit as i64)
+            .await?
+            .try_collect()
+            .await
+    }
+}
+
+/// Everything a track event needs to announce itself and put tracks back on the queue.
+#[derive(Clone)]
+pub struct QueueContext {
+    pub guild_id: GuildId,
     pub channel_id: ChannelId,
     pub http: Arc<Http>,
+    pub songbird: Arc<Songbird>,
+    pub sessions: Arc<MusicSessions>,
+    pub inactivity: Arc<InactivityManager>,
+    pub database: Database,
+    /// Fetches the songs, guarded since direct links come from users.
+    pub request_client: reqwest::Client,
+}
+
+impl QueueContext {
+    pub fn new(ctx: PoiseContext<'_>) -> Self {
+        Self::from_data(
+            &ctx.data(),
+            ctx.serenity_context().http.clone(),
+            ctx.guild_id().unwrap(),
+            ctx.channel_id().expect_channel(),
+        )
+    }
+
+    pub fn from_data(
+        data: &Data,
+        http: Arc<Http>,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Self {
+        Self {
+            guild_id,
+            channel_id,
+            http,
+            songbird: data.songbird.clone(),
+            sessions: data.music_sessions.clone(),
+            inactivity: data.inactivity.clone(),
+            database: data.database(),
+            request_client: guarded_client(),
+        }
+    }
+}
+
+pub struct SongEndNotifier {
+    pub queue: QueueContext,
     pub metadata: Video,
 }
 
+/// Resets the skip vote when a new song starts and announces it unless `play` already did.
 pub struct SongPlayNotifier {
-    pub channel_id: ChannelId,
-    pub http: Arc<Http>,
+    pub queue: QueueContext,
     pub metadata: Video,
+    pub announce: bool,
+}
+
+/// Re-applies the guild's track loop to every track that starts playing.
+pub struct LoopModeHandler {
+    pub guild_id: GuildId,
+    pub sessions: Arc<MusicSessions>,
 }
 
 #[async_trait]
 impl SongbirdEventHandler for SongEndNotifier {
-    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        let guild_id = self.queue.guild_id.get();
+        let mut replaced = false;
+        self.queue
+            .sessions
+            .update(guild_id, |session| {
+                for (_, handle) in tracks.iter() {
+                    replaced |= session.replaced.remove(&handle.uuid());
+                }
+            })
+            .await;
+        if replaced {
+            return None;
+        }
+        // Songs removed from the queue before they started were never played, clearing
+        // or stopping the queue shouldn't announce or loop each of them.
+        if tracks.iter().all(|(state, _)| state.play_time.is_zero()) {
+            return None;
+        }
+
+        for (state, handle) in tracks.iter() {
+            if state.play_time.is_zero() {
+                continue;
+            }
+            let entry = HistoryEntry::new(guild_id, &handle.data::<TrackMetadata>());
+            if let Err(err) = entry.record(&self.queue.database).await {
+                tracing::warn!("Failed to record the history of {}: {}", guild_id, err);
+            }
+        }
+        // The history moved on, `previous` starts from the newest song again.
+        self.queue
+            .sessions
+            .update(guild_id, |session| session.history_cursor = None)
+            .await;
+
         let _ = self
+            .queue
             .channel_id
             .widen()
             .send_message(
-                &self.http,
+                &self.queue.http,
                 Valeriyya::msg_reply().add_embed(
                     Valeriyya::embed()
                         .description(format!("{} has finished.", self.metadata.title))
@@ -404,24 +1459,93 @@ impl SongbirdEventHandler for SongEndNotifier {
             )
             .await;
 
+        for (state, handle) in tracks.iter() {
+            let session = self.queue.sessions.get(guild_id).await;
+            match session.loop_mode {
+                LoopMode::Queue if state.playing == PlayMode::End => {
+                    if let Some(handler_lock) = self.queue.songbird.get(self.queue.guild_id) {
+                        let metadata = handle.data::<TrackMetadata>();
+                        enqueue_video(
+                            &mut *handler_lock.lock().await,
+                            &self.queue,
+                            TrackMetadata::new(metadata.video.clone(), metadata.requester),
+                            state.volume,
+                            session.filter,
+                            true,
+                        );
+                    }
+                }
+                LoopMode::TrackTimes(_) => {
+                    self.queue
+                        .sessions
+                        .update(guild_id, |session| {
+                            session.loop_mode = session.loop_mode.after_song_end()
+                        })
+                        .await;
+                }
+                _ => {}
+            }
+        }
+        // The queue may have run out with this song.
+        self.queue.inactivity.notify(self.queue.guild_id);
+
+        None
+    }
+}
+
+#[async_trait]
+impl SongbirdEventHandler for LoopModeHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ctx else {
+            return None;
+        };
+
+        if self.sessions.get(self.guild_id.get()).await.loop_mode == LoopMode::Track {
+            for (_, handle) in tracks.iter() {
+                let _ = handle.enable_loop();
+            }
+        }
+
         None
     }
 }
 
 #[async_trait]
 impl SongbirdEventHandler for SongPlayNotifier {
-    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            for (_, handle) in tracks.iter() {
+                self.queue
+                    .sessions
+                    .update(self.queue.guild_id.get(), |session| {
+                        if session
+                            .skip_vote
+                            .as_ref()
+                            .is_some_and(|vote| vote.track != handle.uuid())
+                        {
+                            session.skip_vote = None;
+                        }
+                    })
+                    .await;
+            }
+        }
+
+        if !self.announce {
+            return None;
+        }
+
         let _ = self
+            .queue
             .channel_id
             .widen()
             .send_message(
-                &self.http,
+                &self.queue.http,
                 Valeriyya::msg_reply().add_embed(
                     Valeriyya::embed()
                         .description(format!(
                             "Playing [{}]({})",
                             self.metadata.title,
-                            format_args!("https://youtu.be/{}", self.metadata.id)
+                            self.metadata.url()
                         ))
                         .title("Song information"),
                 ),
@@ -431,3 +1555,85 @@ impl SongbirdEventHandler for SongPlayNotifier {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guild_cache_skips_reads_older_than_an_invalidation() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let cache = GuildCache::new(10, Duration::from_secs(60));
+
+        runtime.block_on(async {
+            let generation = cache.generation(1);
+            cache.invalidate(1).await;
+            cache.insert(1, GuildDb::default(), generation).await;
+            assert!(cache.get(1).await.is_none());
+
+            cache
+                .insert(1, GuildDb::default(), cache.generation(1))
+                .await;
+            assert!(cache.get(1).await.is_some());
+        });
+    }
+
+    #[test]
+    fn prefixes_are_stored_longest_first() {
+        let db = GuildDb::default().set_prefixes(vec!["!".into(), "v!!".into(), "v!".into()]);
+        assert_eq!(db.get_prefixes(), ["v!!", "v!", "!"]);
+        assert_eq!(GuildDb::default().get_prefixes(), [DEFAULT_PREFIX]);
+    }
+
+    #[test]
+    fn stored_links_to_private_addresses_are_not_playable() {
+        let track = |id: &str, source| PlaylistTrack {
+            id: id.to_string(),
+            title: String::new(),
+            duration_ms: 0,
+            source,
+        };
+        assert!(track("dQw4w9WgXcQ", TrackSource::YouTube).is_playable());
+        assert!(track("https://example.com/song.mp3", TrackSource::Http).is_playable());
+        assert!(!track("http://127.0.0.1/song.mp3", TrackSource::Http).is_playable());
+        assert!(!track("file:///etc/passwd", TrackSource::Http).is_playable());
+    }
+
+    #[test]
+    fn loop_mode_repeat_count_ends_with_the_song() {
+        assert_eq!(LoopMode::TrackTimes(3).after_song_end(), LoopMode::Off);
+        assert_eq!(LoopMode::Track.after_song_end(), LoopMode::Track);
+        assert_eq!(LoopMode::Queue.after_song_end(), LoopMode::Queue);
+        assert_eq!(LoopMode::Off.after_song_end(), LoopMode::Off);
+    }
+
+    #[test]
+    fn loop_mode_display() {
+        assert_eq!(LoopMode::TrackTimes(2).to_string(), "Track (2 times)");
+        assert_eq!(LoopMode::Queue.to_string(), "Queue");
+    }
+
+    #[test]
+    fn votes_needed_rounds_up_the_percentage() {
+        let music = GuildDbMusic::default();
+        assert_eq!(music.votes_needed(4), 2);
+        assert_eq!(music.votes_needed(5), 3);
+
+        let music = GuildDbMusic {
+            vote_skip: Some(75),
+            ..Default::default()
+        };
+        assert_eq!(music.votes_needed(4), 3);
+    }
+
+    #[test]
+    fn votes_needed_is_at_least_one() {
+        assert_eq!(GuildDbMusic::default().votes_needed(0), 1);
+
+        let music = GuildDbMusic {
+            vote_skip: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(music.votes_needed(10), 1);
+    }
+}
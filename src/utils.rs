@@ -1,25 +1,30 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use bson::doc;
 use chrono::Utc;
-use iso8601_duration::Duration as iso_duration;
-use mongodb::{options::ClientOptions, Client, Database};
+use mongodb::{options::ClientOptions, Client};
 use poise::{
     serenity_prelude::all::{
         Color, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateMessage, EditMessage,
-        Member, RoleId, Timestamp,
+        Member, Permissions, RoleId, Timestamp,
     },
-    CreateReply,
+    ChoiceParameter, CreateReply,
+};
+use serenity::all::{ChannelId, GuildId, Message, UserId};
+use songbird::{
+    input::{HttpRequest, Input, YoutubeDl},
+    tracks::{Track, TrackHandle},
+    Call, Event, TrackEvent,
 };
-use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
-use serde_json::Value;
-use serenity::all::{ChannelId, UserId};
 use tokio::time::sleep;
 
 use crate::{
+    filters::{AudioFilter, FilteredSource},
+    resolver::{PendingTracks, Resolved, ResolverContext},
     structs::{
-        GuildDb, Reminder, ResponsePlaylistApi, ResponseSearchVideoApi, ResponseVideoApi,
-        SearchVideoItem, SpotifyQuery, Video,
+        CommandCategory, GuildDb, LoopModeHandler, MetadataError, PlaylistTrack, QueueContext,
+        Reminder, SavedQueue, SavedQueueTrack, SongEndNotifier, SongPlayNotifier, TrackMetadata,
+        TrackSource, Video, DEFAULT_PLAYLIST_LIMIT, DEFAULT_PREFIX, QUEUE_SAVE_INTERVAL,
     },
     Context, Data, Error,
 };
@@ -49,259 +54,6 @@ macro_rules! regex_lazy {
     };
 }
 
-fn extract_spotify_id(url: &str) -> Option<(String, String)> {
-    let re = regex::Regex::new(r"open\.spotify\.com/(track|playlist)/([a-zA-Z0-9]+)").unwrap();
-    re.captures(url)
-        .map(|caps| (caps[1].to_string(), caps[2].to_string()))
-}
-
-pub async fn get_spotify_queries(
-    url: &str,
-    api_key: &str,
-    reqwest: &reqwest::Client,
-) -> Option<SpotifyQuery> {
-    let (kind, id) = extract_spotify_id(url)?;
-
-    match kind.as_str() {
-        "track" => {
-            let api_url = format!("https://api.spotify.com/v1/tracks/{}", id);
-            let response = reqwest
-                .get(api_url)
-                .header(AUTHORIZATION, format!("Bearer {}", api_key))
-                .header(CONTENT_TYPE, "application/json")
-                .header(ACCEPT, "application/json")
-                .send()
-                .await
-                .ok()?
-                .json::<Value>()
-                .await
-                .ok()?;
-
-            let name = response.get("name")?.as_str()?;
-            let artist = response.get("artists")?.get(0)?.get("name")?.as_str()?;
-            Some(SpotifyQuery::Track(format!("{} {}", name, artist)))
-        }
-        "playlist" | "album" => {
-            let mut queries = Vec::new();
-            let mut next_url = Some(format!(
-                "https://api.spotify.com/v1/playlists/{}/tracks?limit=100",
-                id
-            ));
-
-            while let Some(url) = next_url {
-                let response = reqwest
-                    .get(&url)
-                    .header(AUTHORIZATION, format!("Bearer {}", api_key))
-                    .header(CONTENT_TYPE, "application/json")
-                    .header(ACCEPT, "application/json")
-                    .send()
-                    .await
-                    .ok()?
-                    .json::<Value>()
-                    .await
-                    .ok()?;
-
-                if let Some(items) = response.get("items").and_then(|v| v.as_array()) {
-                    for item in items {
-                        if let Some(track) = item.get("track") {
-                            let name = track.get("name").and_then(|n| n.as_str());
-                            let artist = track
-                                .get("artists")
-                                .and_then(|a| a.get(0))
-                                .and_then(|a| a.get("name"))
-                                .and_then(|n| n.as_str());
-                            if let (Some(name), Some(artist)) = (name, artist) {
-                                queries.push(format!("{} {}", name, artist));
-                            }
-                        }
-                    }
-                }
-
-                next_url = response
-                    .get("next")
-                    .and_then(|n| n.as_str())
-                    .map(|s| s.to_string());
-            }
-            Some(SpotifyQuery::Playlist(queries))
-        }
-        _ => None,
-    }
-}
-
-async fn search_video(
-    query: impl Into<String>,
-    api_key: &String,
-    reqwest: &reqwest::Client,
-) -> SearchVideoItem {
-    let url = format!(
-        "https://youtube.googleapis.com/youtube/v3/search?part=snippet&order=relevance&type=video&maxResults=1&q={}&key={}", 
-        query.into(),
-        api_key
-    );
-    let video = reqwest
-        .get(url)
-        .send()
-        .await
-        .expect("Error getting Video search.")
-        .json::<ResponseSearchVideoApi>()
-        .await
-        .expect("Error parsing the Video search JSON.");
-    video.items
-        .first()
-        .expect("Error getting the first Video search.")
-        .clone()
-}
-
-async fn get_metadata(ctx: Context<'_>, url: impl Into<String>) -> Vec<Video> {
-    let url = url.into();
-    let reqwest_client = reqwest::Client::new();
-    let data = ctx.data();
-    let mut token_manager = data.api_token_manager.lock().await;
-    let spotify_api_key = token_manager.get_spotify_token().await.unwrap().clone();
-    let youtube_api_key = token_manager.get_youtube_token().clone();
-
-    // Check if it's a YouTube playlist
-    if let Some(youtube_id) =
-        regex!(r"(?:(?:PL|LL|EC|UU|FL|RD|UL|TL|PU|OLAK5uy_)[0-9A-Za-z-_]{10,}|RDMM)")
-            .find(&url)
-            .map(|u| u.as_str().to_owned())
-    {
-        let request_playlist_url = format!(
-            "https://youtube.googleapis.com/youtube/v3/playlistItems?part=snippet%2CcontentDetails&maxResults=100&playlistId={}&key={}",
-            youtube_id,
-            youtube_api_key
-        );
-        let playlist_items = reqwest_client
-            .get(request_playlist_url)
-            .send()
-            .await
-            .expect("Error getting Playlist JSON.")
-            .json::<ResponsePlaylistApi>()
-            .await
-            .expect("Error parsing the Playlist JSON.")
-            .items;
-
-        let mut video_ids: Vec<String> = Vec::with_capacity(100);
-        for item in playlist_items.into_iter() {
-            video_ids.push(item.snippet.resource_id.video_id);
-        }
-
-        let request_videos_url = format!(
-            "https://youtube.googleapis.com/youtube/v3/videos?part=snippet%2CcontentDetails&id={}&key={}",
-            video_ids.join(","),
-            youtube_api_key
-        );
-
-        let video_items = reqwest_client
-            .get(request_videos_url)
-            .send()
-            .await
-            .expect("Error getting Videos from the Video Id Vector.")
-            .json::<ResponseVideoApi>()
-            .await
-            .expect("Error parsing the Videos JSON.")
-            .items;
-
-        let mut videos: Vec<Video> = Vec::with_capacity(100);
-        for item in video_items.into_iter() {
-            let duration = iso_duration::parse(&item.content_details.duration)
-                .unwrap()
-                .to_std()
-                .unwrap();
-            videos.push(Video {
-                id: item.id,
-                title: item.snippet.title,
-                duration,
-            });
-        }
-        return videos;
-    }
-    // Check if the url is a Spotify playlist
-    else if let Some((kind, spotify_id)) = extract_spotify_id(&url) {
-        if kind == "playlist" {
-            let playlist_url = format!("https://open.spotify.com/playlist/{}", spotify_id);
-            let tracks =
-                get_spotify_queries(&playlist_url, &spotify_api_key, &reqwest_client).await;
-            let mut videos = Vec::new();
-            if let Some(SpotifyQuery::Playlist(queries)) = tracks {
-                for query in queries {
-                    let video = search_video(&query, &youtube_api_key, &reqwest_client).await;
-                    let request_video_url = format!(
-                        "https://youtube.googleapis.com/youtube/v3/videos?part=snippet%2CcontentDetails&id={}&key={}",
-                        video.id.video_id,
-                        youtube_api_key
-                    );
-                    let item = reqwest_client
-                        .get(&request_video_url)
-                        .send()
-                        .await
-                        .expect("Error getting Video JSON")
-                        .json::<ResponseVideoApi>()
-                        .await
-                        .expect("Error parsing the Video JSON.")
-                        .items
-                        .first()
-                        .expect("There is no video from this url.")
-                        .clone();
-                    let duration = iso_duration::parse(&item.content_details.duration)
-                        .unwrap()
-                        .to_std()
-                        .unwrap();
-                    videos.push(Video {
-                        id: item.id,
-                        title: item.snippet.title,
-                        duration,
-                    });
-                }
-            }
-            return videos;
-        }
-    }
-
-    let id = match get_spotify_queries(&url, &spotify_api_key, &reqwest_client).await {
-        Some(SpotifyQuery::Track(search_query)) => {
-            search_video(&search_query, &youtube_api_key, &reqwest_client)
-                .await
-                .id
-                .video_id
-        }
-        _ => {
-            search_video(&url, &youtube_api_key, &reqwest_client)
-                .await
-                .id
-                .video_id
-        }
-    };
-
-    let request_video_url = format!(
-        "https://youtube.googleapis.com/youtube/v3/videos?part=snippet%2CcontentDetails&id={}&key={}",
-        id,
-        youtube_api_key
-    );
-
-    let item = reqwest_client
-        .get(request_video_url)
-        .send()
-        .await
-        .expect("Error getting Video JSON")
-        .json::<ResponseVideoApi>()
-        .await
-        .expect("Error parsing the Video JSON.")
-        .items
-        .first()
-        .expect("There is no video from this url.")
-        .clone();
-    let duration = iso_duration::parse(&item.content_details.duration)
-        .unwrap()
-        .to_std()
-        .unwrap();
-    vec![Video {
-        id: item.id,
-        title: item.snippet.title,
-        duration,
-    }]
-}
-
 fn string_to_sec(raw_text: impl ToString) -> i64 {
     let re = regex_lazy!(
         r"((?P<years>\d+?)\s??y|year|years)?((?P<months>\d+?)\s??month|months)?((?P<weeks>\d+?)\s??w|week|weeks)?((?P<days>\d+?)\s??d|day|days)?((?P<hours>\d+?\s??)h|hour|hours)?((?P<minutes>\d+?)\s??m|min|minutes)?((?P<seconds>\d+?)\s??s|sec|second|seconds)?"
@@ -339,6 +91,252 @@ fn string_to_sec(raw_text: impl ToString) -> i64 {
     seconds
 }
 
+/// Enqueues a resolved video on the call with its metadata attached to the track, playing it
+/// through ffmpeg when it is filtered or starts partway into the song.
+pub fn enqueue_video(
+    handler: &mut Call,
+    queue: &QueueContext,
+    mut metadata: TrackMetadata,
+    volume: f32,
+    filter: AudioFilter,
+    notify_play: bool,
+) -> TrackHandle {
+    let source: Input = if !filter.is_off() || !metadata.start.is_zero() {
+        metadata.speed = filter.speed;
+        FilteredSource {
+            video: metadata.video.clone(),
+            filter,
+            start: metadata.start,
+        }
+        .into()
+    } else {
+        match metadata.video.source {
+            TrackSource::YouTube => {
+                YoutubeDl::new(queue.request_client.clone(), metadata.video.id.clone()).into()
+            }
+            TrackSource::Http => {
+                HttpRequest::new(queue.request_client.clone(), metadata.video.id.clone()).into()
+            }
+        }
+    };
+    let preload = metadata
+        .video
+        .duration
+        .saturating_sub(metadata.start)
+        .div_f64(metadata.speed)
+        .saturating_sub(Duration::from_secs(15));
+    let video = metadata.video.clone();
+
+    let track = handler.enqueue_with_preload(
+        Track::new_with_data(source, Arc::new(metadata)).volume(volume),
+        Some(preload),
+    );
+    let _ = track.add_event(
+        Event::Track(TrackEvent::End),
+        SongEndNotifier {
+            queue: queue.clone(),
+            metadata: video.clone(),
+        },
+    );
+    let _ = track.add_event(
+        Event::Track(TrackEvent::Play),
+        LoopModeHandler {
+            guild_id: queue.guild_id,
+            sessions: queue.sessions.clone(),
+        },
+    );
+    let _ = track.add_event(
+        Event::Track(TrackEvent::Play),
+        SongPlayNotifier {
+            queue: queue.clone(),
+            metadata: video,
+            announce: notify_play,
+        },
+    );
+
+    track
+}
+
+/// Appends tracks to the queue as the resolver finishes looking them up in the background, and
+/// says how many could not be found once it is done.
+pub fn enqueue_pending(
+    mut pending: PendingTracks,
+    queue: QueueContext,
+    requester: UserId,
+    volume: f32,
+) {
+    tokio::spawn(async move {
+        let guild_id = queue.guild_id.get();
+        let clears = queue.sessions.get(guild_id).await.clears;
+        while let Some(videos) = pending.receiver.recv().await {
+            let session = queue.sessions.get(guild_id).await;
+            // The rest was loading while the queue got stopped or cleared.
+            if session.clears != clears {
+                return;
+            }
+            let Some(handler_lock) = queue.songbird.get(queue.guild_id) else {
+                return;
+            };
+            let mut handler = handler_lock.lock().await;
+
+            for video in videos {
+                enqueue_video(
+                    &mut handler,
+                    &queue,
+                    TrackMetadata::new(video, requester),
+                    volume,
+                    session.filter,
+                    true,
+                );
+            }
+        }
+
+        let skipped = pending.skipped.await.unwrap_or(0);
+        if skipped > 0 {
+            let _ = queue
+                .channel_id
+                .widen()
+                .send_message(
+                    &queue.http,
+                    Valeriyya::msg_reply().add_embed(
+                        Valeriyya::embed()
+                            .description(format!(
+                                "Skipped {} songs that could not be found.",
+                                skipped
+                            ))
+                            .title("Song information"),
+                    ),
+                )
+                .await;
+        }
+    });
+}
+
+/// Enqueues the songs of the queue again with the guild's filter, continuing the current song
+/// from `position`. The current track keeps playing until its replacement is ready.
+pub async fn restart_queue(handler: &mut Call, queue: &QueueContext, position: Duration) {
+    let tracks = handler.queue().current_queue();
+    let Some(current) = tracks.first() else {
+        return;
+    };
+    let volume = current
+        .get_info()
+        .await
+        .map(|state| state.volume)
+        .unwrap_or(1.0);
+
+    let guild_id = queue.guild_id.get();
+    let filter = queue.sessions.get(guild_id).await.filter;
+    queue
+        .sessions
+        .update(guild_id, |session| {
+            session
+                .replaced
+                .extend(tracks.iter().map(|track| track.uuid()))
+        })
+        .await;
+
+    let upcoming = handler
+        .queue()
+        .modify_queue(|queued| queued.drain(1..).collect::<Vec<_>>());
+    for track in upcoming {
+        let _ = track.stop();
+    }
+
+    for (i, track) in tracks.iter().enumerate() {
+        let old = track.data::<TrackMetadata>();
+        let mut metadata = TrackMetadata::new(old.video.clone(), old.requester);
+        if i == 0 {
+            metadata.start = position;
+        }
+        enqueue_video(handler, queue, metadata, volume, filter, i >= 1);
+    }
+
+    let _ = handler.queue().skip();
+}
+
+/// Joins `connect_to` and queues the resolved tracks, returning the description of what was
+/// queued for the reply.
+pub async fn play_resolved(
+    ctx: Context<'_>,
+    connect_to: ChannelId,
+    resolved: Resolved,
+) -> Result<String, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let metadata = resolved.tracks;
+    let _ = ctx.data().songbird.join(guild_id, connect_to).await;
+
+    let Some(handler_lock) = ctx.data().songbird.get(guild_id) else {
+        return Ok("I could not join your voice channel.".to_string());
+    };
+    let mut handler = handler_lock.lock().await;
+
+    let volume = Valeriyya::get_database(&ctx.data(), guild_id.get())
+        .await?
+        .music
+        .track_volume();
+
+    let queue = QueueContext::new(ctx);
+    ctx.data()
+        .music_sessions
+        .update(guild_id.get(), |session| {
+            session.text_channel = Some(queue.channel_id)
+        })
+        .await;
+    let filter = ctx.data().music_sessions.get(guild_id.get()).await.filter;
+
+    for (i, video) in metadata.iter().enumerate() {
+        enqueue_video(
+            &mut handler,
+            &queue,
+            TrackMetadata::new(video.clone(), ctx.author().id),
+            volume,
+            filter,
+            metadata.len() >= 2 && i >= 1,
+        );
+    }
+
+    let pending_count = resolved.pending.as_ref().map_or(0, |pending| pending.count);
+    if let Some(pending) = resolved.pending {
+        enqueue_pending(pending, queue, ctx.author().id, volume);
+    }
+
+    ctx.data()
+        .inactivity
+        .watch(ctx.serenity_context(), guild_id);
+
+    let information_title = if metadata.len() + pending_count > 1 {
+        "Queued"
+    } else {
+        "Playing"
+    };
+
+    let mut summary = String::new();
+    if metadata.len() > 1 {
+        summary.push_str(&format!("\nAdded {} songs to the queue.", metadata.len()));
+    }
+    if pending_count > 0 {
+        summary.push_str(&format!(
+            "\nLoading {} more songs in the background.",
+            pending_count
+        ));
+    }
+    if resolved.skipped > 0 {
+        summary.push_str(&format!(
+            "\nSkipped {} private, deleted or unavailable songs.",
+            resolved.skipped
+        ));
+    }
+
+    Ok(format!(
+        "{} [{}]({}){}",
+        information_title,
+        metadata[0].title,
+        metadata[0].url(),
+        summary
+    ))
+}
+
 pub async fn get_guild_member(ctx: Context<'_>) -> Result<Option<Member>, Error> {
     Ok(match ctx.guild_id() {
         Some(guild_id) => Some(
@@ -350,6 +348,160 @@ pub async fn get_guild_member(ctx: Context<'_>) -> Result<Option<Member>, Error>
     })
 }
 
+/// Checks if the author can use a command of the category, either through their Discord
+/// permissions or through a role the guild granted the category to.
+async fn category_access(
+    ctx: Context<'_>,
+    category: CommandCategory,
+    required: Permissions,
+) -> Result<bool, Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(true),
+    };
+    match ctx.author_member().await {
+        Some(member) => member_access(ctx, guild_id, &member, category, required).await,
+        None => Ok(false),
+    }
+}
+
+/// Like `category_access`, for a member other than the author, e.g. someone pressing a button.
+async fn member_access(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    member: &Member,
+    category: CommandCategory,
+    required: Permissions,
+) -> Result<bool, Error> {
+    let permissions = member.permissions.unwrap_or_else(|| {
+        ctx.guild()
+            .map(|guild| guild.member_permissions(member))
+            .unwrap_or_else(Permissions::empty)
+    });
+    granted_access(
+        &ctx.data(),
+        guild_id,
+        member,
+        permissions,
+        category,
+        required,
+    )
+    .await
+}
+
+/// The check behind `category_access`, for places without a command context such as the
+/// appeal buttons, where the member's permissions come with the interaction.
+pub async fn granted_access(
+    data: &Data,
+    guild_id: GuildId,
+    member: &Member,
+    permissions: Permissions,
+    category: CommandCategory,
+    required: Permissions,
+) -> Result<bool, Error> {
+    if permissions.administrator() || permissions.contains(required) {
+        return Ok(true);
+    }
+
+    let db = Valeriyya::get_database(data, guild_id.get()).await?;
+    let granted = db.roles.granted_roles(category);
+
+    Ok(member
+        .roles
+        .iter()
+        .any(|role_id| granted.contains(&role_id.to_string())))
+}
+
+async fn deny_access(ctx: Context<'_>, category: CommandCategory) -> Result<bool, Error> {
+    ctx.send(
+        Valeriyya::reply(format!(
+            "You don't have the permissions or a role granted for {} commands.",
+            category.name()
+        ))
+        .ephemeral(true),
+    )
+    .await?;
+    Ok(false)
+}
+
+/// Global command check for the categories that can be granted to roles through `settings permission`.
+pub async fn command_check(ctx: Context<'_>) -> Result<bool, Error> {
+    let category = match ctx.command().category.as_deref() {
+        Some("Moderation") => CommandCategory::Moderation,
+        Some("Settings") => CommandCategory::Settings,
+        _ => return Ok(true),
+    };
+
+    // The commands stay visible to everyone so granted roles can find them, the Discord
+    // permissions they need without a grant are declared as their `custom_data`.
+    let required = ctx
+        .parent_commands()
+        .iter()
+        .copied()
+        .chain([ctx.command()])
+        .filter_map(|command| command.custom_data.downcast_ref::<Permissions>())
+        .copied()
+        .reduce(|required, permissions| required | permissions)
+        .unwrap_or(Permissions::ADMINISTRATOR);
+
+    if category_access(ctx, category, required).await? {
+        Ok(true)
+    } else {
+        deny_access(ctx, category).await
+    }
+}
+
+/// Command check for music commands that control playback for everyone in the channel.
+pub async fn dj_check(ctx: Context<'_>) -> Result<bool, Error> {
+    if can_control_playback(ctx, ctx.author().id).await? {
+        Ok(true)
+    } else {
+        deny_access(ctx, CommandCategory::Dj).await
+    }
+}
+
+/// Whether the user may control playback for everyone, used by `dj_check` and the player
+/// buttons. Anyone can until the guild grants the DJ category to a role, then only DJs.
+pub async fn can_control_playback(ctx: Context<'_>, user_id: UserId) -> Result<bool, Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(true),
+    };
+
+    let db = Valeriyya::get_database(&ctx.data(), guild_id.get()).await?;
+    if db.roles.granted_roles(CommandCategory::Dj).is_empty() {
+        return Ok(true);
+    }
+
+    if user_id == ctx.author().id {
+        is_dj(ctx).await
+    } else {
+        is_member_dj(ctx, user_id).await
+    }
+}
+
+/// Whether the author has the DJ role, a role granted the DJ category or can manage channels.
+pub async fn is_dj(ctx: Context<'_>) -> Result<bool, Error> {
+    category_access(ctx, CommandCategory::Dj, Permissions::MANAGE_CHANNELS).await
+}
+
+/// Whether another member of the guild counts as a DJ, see `is_dj`.
+pub async fn is_member_dj(ctx: Context<'_>, user_id: UserId) -> Result<bool, Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(true),
+    };
+    let member = guild_id.member(ctx.serenity_context(), user_id).await?;
+    member_access(
+        ctx,
+        guild_id,
+        &member,
+        CommandCategory::Dj,
+        Permissions::MANAGE_CHANNELS,
+    )
+    .await
+}
+
 pub async fn member_managable<'a>(ctx: Context<'_>, member: &Member) -> bool {
     let bot_id = ctx.serenity_context().cache.current_user().id;
     {
@@ -409,7 +561,7 @@ pub fn compare_role_position(ctx: Context<'_>, role1: RoleId, role2: RoleId) ->
     }
 }
 
-pub async fn reminder_checker(ctx: Arc<serenity::prelude::Context>, database: Database) {
+pub async fn reminder_checker(ctx: Arc<serenity::prelude::Context>, data: Arc<Data>) {
     loop {
         let now = Utc::now();
 
@@ -418,7 +570,7 @@ pub async fn reminder_checker(ctx: Arc<serenity::prelude::Context>, database: Da
         for guild_id in guilds {
             let guild_id_u64 = guild_id.get();
 
-            let db = Valeriyya::get_database(&database, guild_id_u64)
+            let db = Valeriyya::get_database(&data, guild_id_u64)
                 .await
                 .expect("Failed to fetch database");
 
@@ -436,11 +588,11 @@ pub async fn reminder_checker(ctx: Arc<serenity::prelude::Context>, database: Da
                     }
                 });
 
-                let mut db = Valeriyya::get_database(&database, guild_id_u64)
+                let mut db = Valeriyya::get_database(&data, guild_id_u64)
                     .await
                     .expect("Failed to fetch database");
                 db = db.remove_reminder(reminder.id);
-                db.execute(&database).await;
+                db.execute(&data).await;
             }
         }
 
@@ -448,6 +600,171 @@ pub async fn reminder_checker(ctx: Arc<serenity::prelude::Context>, database: Da
     }
 }
 
+/// Saves every guild's queue so `restore_queues` can bring it back after a restart. A call that
+/// lost its connection keeps the queue it last saved.
+pub async fn queue_saver(data: Arc<Data>) {
+    loop {
+        sleep(QUEUE_SAVE_INTERVAL).await;
+
+        let calls: Vec<_> = data.songbird.iter().collect();
+        for (guild_id, handler_lock) in calls {
+            let guild_id = guild_id.0.get();
+            let (voice_channel, tracks) = {
+                let handler = handler_lock.lock().await;
+                (handler.current_channel(), handler.queue().current_queue())
+            };
+
+            if tracks.is_empty() {
+                let _ = SavedQueue::delete(&data, guild_id).await;
+                continue;
+            }
+
+            let session = data.music_sessions.get(guild_id).await;
+            let (Some(voice_channel), Some(text_channel)) = (voice_channel, session.text_channel)
+            else {
+                continue;
+            };
+
+            let position = tracks[0]
+                .get_info()
+                .await
+                .map(|state| tracks[0].data::<TrackMetadata>().position(state.position))
+                .unwrap_or_default();
+
+            let saved = SavedQueue {
+                guild_id,
+                voice_channel: voice_channel.0.get(),
+                text_channel: text_channel.get(),
+                tracks: tracks
+                    .iter()
+                    .map(|track| {
+                        let metadata = track.data::<TrackMetadata>();
+                        SavedQueueTrack {
+                            track: PlaylistTrack::from(&metadata.video),
+                            requester: metadata.requester.get(),
+                        }
+                    })
+                    .collect(),
+                position_ms: position.as_millis() as u64,
+                loop_mode: session.loop_mode,
+            };
+
+            if let Err(err) = saved.save(&data).await {
+                tracing::warn!("Failed to save the queue of {}: {}", guild_id, err);
+            }
+        }
+    }
+}
+
+/// Rejoins the voice channels of the saved queues and queues their songs again, continuing the
+/// current song where it was left.
+pub async fn restore_queues(ctx: serenity::prelude::Context, data: Arc<Data>) {
+    let saved_queues = match SavedQueue::all(&data).await {
+        Ok(saved_queues) => saved_queues,
+        Err(err) => {
+            tracing::warn!("Failed to load the saved queues: {}", err);
+            return;
+        }
+    };
+
+    for saved in saved_queues {
+        // Ready fires again on reconnects, while the queue is still playing.
+        if let Some(handler_lock) = data.songbird.get(GuildId::new(saved.guild_id)) {
+            if !handler_lock.lock().await.queue().is_empty() {
+                continue;
+            }
+        }
+
+        let guild_id = saved.guild_id;
+        if let Err(err) = restore_queue(&ctx, &data, saved).await {
+            tracing::warn!("Failed to restore the queue of {}: {}", guild_id, err);
+            // Otherwise every reconnect would try the same broken queue again.
+            if let Err(err) = SavedQueue::delete(&data, guild_id).await {
+                tracing::warn!("Failed to delete the saved queue of {}: {}", guild_id, err);
+            }
+        }
+    }
+}
+
+async fn restore_queue(
+    ctx: &serenity::prelude::Context,
+    data: &Data,
+    saved: SavedQueue,
+) -> Result<(), Error> {
+    let guild_id = GuildId::new(saved.guild_id);
+    let text_channel = ChannelId::new(saved.text_channel);
+
+    let resume = saved
+        .tracks
+        .first()
+        .is_some_and(|saved_track| saved_track.track.is_playable());
+    let tracks: Vec<_> = saved
+        .tracks
+        .into_iter()
+        .filter(|saved_track| saved_track.track.is_playable())
+        .collect();
+    if tracks.is_empty() {
+        return Err("none of the saved songs can be played".into());
+    }
+
+    let handler_lock = data
+        .songbird
+        .join(guild_id, ChannelId::new(saved.voice_channel))
+        .await?;
+
+    let volume = Valeriyya::get_database(data, saved.guild_id)
+        .await?
+        .music
+        .track_volume();
+
+    data.music_sessions
+        .update(saved.guild_id, |session| {
+            session.loop_mode = saved.loop_mode;
+            session.text_channel = Some(text_channel);
+        })
+        .await;
+
+    let queue = QueueContext::from_data(data, ctx.http.clone(), guild_id, text_channel);
+
+    let count = tracks.len();
+    let mut handler = handler_lock.lock().await;
+    for (i, saved_track) in tracks.into_iter().enumerate() {
+        let mut metadata =
+            TrackMetadata::new(saved_track.track.into(), UserId::new(saved_track.requester));
+        // Started through ffmpeg at the position, like a restarted queue.
+        if i == 0 && resume {
+            metadata.start = Duration::from_millis(saved.position_ms);
+        }
+        enqueue_video(
+            &mut handler,
+            &queue,
+            metadata,
+            volume,
+            AudioFilter::default(),
+            i >= 1,
+        );
+    }
+    drop(handler);
+    data.inactivity.watch(ctx, guild_id);
+
+    text_channel
+        .widen()
+        .send_message(
+            &ctx.http,
+            Valeriyya::msg_reply().embed(
+                Valeriyya::embed()
+                    .description(format!(
+                        "Restored the queue with {} songs after a restart.",
+                        count
+                    ))
+                    .title("Queue restored"),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
 pub async fn send_reminder(
     ctx: Arc<serenity::prelude::Context>,
     channel_id: ChannelId,
@@ -462,6 +779,29 @@ pub async fn send_reminder(
     Ok(())
 }
 
+pub async fn strip_prefix<'a>(
+    _ctx: &'a serenity::prelude::Context,
+    msg: &'a Message,
+    data: &'a Data,
+) -> Result<Option<(&'a str, &'a str)>, Error> {
+    let prefixes = match msg.guild_id {
+        Some(guild_id) => Valeriyya::get_database(data, guild_id.get())
+            .await?
+            .get_prefixes(),
+        None => vec![DEFAULT_PREFIX.to_string()],
+    };
+
+    Ok(split_prefix(&msg.content, &prefixes))
+}
+
+/// Splits off the first of the guild's prefixes the content starts with.
+fn split_prefix<'a>(content: &'a str, prefixes: &[String]) -> Option<(&'a str, &'a str)> {
+    prefixes
+        .iter()
+        .find(|prefix| !prefix.is_empty() && content.starts_with(prefix.as_str()))
+        .map(|prefix| content.split_at(prefix.len()))
+}
+
 pub async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
     match error {
         poise::FrameworkError::Command { error, ctx, .. } => {
@@ -540,14 +880,123 @@ impl Valeriyya {
         string_to_sec(raw_text)
     }
 
+    pub fn format_duration(duration: Duration) -> String {
+        let seconds = duration.as_secs();
+        let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+        if hours > 0 {
+            format!("{}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            format!("{}:{:02}", minutes, seconds)
+        }
+    }
+
     pub async fn get_database(
-        db: &Database,
+        data: &Data,
         guild_id: u64,
     ) -> Result<GuildDb, mongodb::error::Error> {
-        GuildDb::new(db, guild_id.to_string()).await
+        if let Some(guild) = data.guild_cache.get(guild_id).await {
+            return Ok(guild);
+        }
+        let generation = data.guild_cache.generation(guild_id);
+
+        let guild = GuildDb::new(&data.database(), guild_id.to_string()).await?;
+        data.guild_cache
+            .insert(guild_id, guild.clone(), generation)
+            .await;
+        Ok(guild)
+    }
+
+    async fn resolver_context(ctx: Context<'_>) -> ResolverContext {
+        let data = ctx.data();
+        let playlist_limit = match ctx.guild_id() {
+            Some(guild_id) => Valeriyya::get_database(&data, guild_id.get())
+                .await
+                .map_or(DEFAULT_PLAYLIST_LIMIT, |db| db.music.playlist_limit()),
+            None => DEFAULT_PLAYLIST_LIMIT,
+        };
+        ResolverContext::new(&data, playlist_limit).await
+    }
+
+    pub async fn get_metadata(
+        ctx: Context<'_>,
+        url: impl Into<String>,
+    ) -> Result<Resolved, MetadataError> {
+        let resolver_ctx = Valeriyya::resolver_context(ctx).await;
+        ctx.data()
+            .resolver
+            .resolve(&url.into(), &resolver_ctx)
+            .await
+    }
+
+    pub async fn search(
+        ctx: Context<'_>,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Video>, MetadataError> {
+        let resolver_ctx = Valeriyya::resolver_context(ctx).await;
+        ctx.data()
+            .resolver
+            .search(query, limit, &resolver_ctx)
+            .await
+    }
+
+    /// Search suggestions that don't spend YouTube Data API quota, for autocompletion.
+    pub async fn suggest(ctx: Context<'_>, query: &str, limit: usize) -> Vec<Video> {
+        let resolver_ctx = Valeriyya::resolver_context(ctx).await;
+        ctx.data()
+            .resolver
+            .suggest(query, limit, &resolver_ctx)
+            .await
+    }
+
+    /// The voice channel the author is connected to in this guild.
+    pub fn author_voice_channel(ctx: Context<'_>) -> Option<ChannelId> {
+        ctx.guild()?
+            .voice_states
+            .get(&ctx.author().id)
+            .and_then(|voice_state| voice_state.channel_id)
+    }
+
+    /// Cuts the text down to `max` characters for fields Discord limits in length.
+    pub fn truncate(text: &str, max: usize) -> String {
+        if text.chars().count() <= max {
+            return text.to_string();
+        }
+        let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefixes(prefixes: &[&str]) -> Vec<String> {
+        prefixes.iter().map(|prefix| prefix.to_string()).collect()
+    }
+
+    #[test]
+    fn split_prefix_matches_a_guild_prefix() {
+        let prefixes = prefixes(&["!", "v!"]);
+        assert_eq!(
+            split_prefix("v!play song", &prefixes),
+            Some(("v!", "play song"))
+        );
+        assert_eq!(split_prefix("!skip", &prefixes), Some(("!", "skip")));
+        assert_eq!(split_prefix("play song", &prefixes), None);
+    }
+
+    #[test]
+    fn split_prefix_takes_the_first_match() {
+        let prefixes = prefixes(&["!!", "!"]);
+        assert_eq!(split_prefix("!!queue", &prefixes), Some(("!!", "queue")));
+        assert_eq!(split_prefix("!queue", &prefixes), Some(("!", "queue")));
     }
 
-    pub async fn get_metadata(ctx: Context<'_>, url: impl Into<String>) -> Vec<Video> {
-        get_metadata(ctx, url).await
+    #[test]
+    fn split_prefix_ignores_empty_prefixes() {
+        assert_eq!(split_prefix("hello", &prefixes(&[""])), None);
+        assert_eq!(split_prefix("", &prefixes(&["!"])), None);
     }
 }